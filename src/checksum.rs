@@ -0,0 +1,69 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::digest::{digest_file, Algorithm};
+
+/// An expected digest, written on the command line as `<algo>:<hex>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    pub algorithm: Algorithm,
+    pub expected: String,
+}
+
+impl Checksum {
+    pub fn new(algorithm: Algorithm, expected: &str) -> Result<Self, String> {
+        let expected = expected.to_ascii_lowercase();
+
+        if expected.len() != algorithm.hex_len() || !expected.bytes().all(|b| b.is_ascii_hexdigit())
+        {
+            return Err(format!(
+                "Invalid {} checksum '{}': expected {} hex digits",
+                algorithm,
+                expected,
+                algorithm.hex_len()
+            ));
+        }
+
+        Ok(Checksum {
+            algorithm,
+            expected,
+        })
+    }
+
+    /// Hashes `path` and compares it with the expected digest. `name` is
+    /// the file the error message refers to, which can differ from `path`
+    /// while a download is still in its temporary file.
+    pub fn verify<P: AsRef<Path>>(&self, path: P, name: &str) -> Result<(), String> {
+        let path = path.as_ref();
+        let actual = digest_file(self.algorithm, path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+        if actual == self.expected {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} checksum mismatch for {}: expected {}, got {}",
+                self.algorithm, name, self.expected, actual
+            ))
+        }
+    }
+}
+
+impl FromStr for Checksum {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (algorithm, hex) = s
+            .split_once(':')
+            .ok_or(format!("Invalid checksum '{}': expected <algo>:<hex>", s))?;
+
+        Checksum::new(algorithm.parse()?, hex)
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.algorithm, self.expected)
+    }
+}
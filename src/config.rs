@@ -0,0 +1,329 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::checksum::Checksum;
+use crate::filename::SanitizeProfile;
+use crate::report::ReportFormat;
+use crate::shell;
+use crate::toml::{self, Value};
+
+/// Which program performs the transfers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// curl if it can be found, otherwise the native backend.
+    Auto,
+    Curl,
+    Native,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Backend::Auto),
+            "curl" => Ok(Backend::Curl),
+            "native" => Ok(Backend::Native),
+            _ => Err(format!(
+                "Unknown backend '{}': expected auto, curl or native",
+                s
+            )),
+        }
+    }
+}
+
+/// What to do when a download's output path already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnConflict {
+    /// Report an error and leave the existing file alone.
+    Fail,
+    Overwrite,
+    /// Save the download as `name (1).ext`, `name (2).ext` and so on.
+    Rename,
+    /// Keep the existing file and don't download it again.
+    Skip,
+}
+
+impl FromStr for OnConflict {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fail" => Ok(OnConflict::Fail),
+            "overwrite" => Ok(OnConflict::Overwrite),
+            "rename" => Ok(OnConflict::Rename),
+            "skip" => Ok(OnConflict::Skip),
+            _ => Err(format!(
+                "Unknown conflict policy '{}': expected fail, overwrite, rename or skip",
+                s
+            )),
+        }
+    }
+}
+
+/// What to do when several URLs would be saved to the same file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnCollision {
+    /// Stop before downloading anything and list the collisions.
+    Abort,
+    /// Prefix colliding filenames derived from URLs with their host.
+    PrefixHost,
+}
+
+impl FromStr for OnCollision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "abort" => Ok(OnCollision::Abort),
+            "prefix-host" => Ok(OnCollision::PrefixHost),
+            _ => Err(format!(
+                "Unknown collision policy '{}': expected abort or prefix-host",
+                s
+            )),
+        }
+    }
+}
+
+/// How wcurl's exit status is chosen when a download fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCodeMode {
+    /// Exit with curl's own exit code, falling back to 1.
+    Curl,
+    /// Always exit with 1.
+    Simple,
+}
+
+impl FromStr for ExitCodeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "curl" => Ok(ExitCodeMode::Curl),
+            "simple" => Ok(ExitCodeMode::Simple),
+            _ => Err(format!(
+                "Unknown exit code mode '{}': expected curl or simple",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub curl_options: Vec<String>,
+    pub urls: Vec<String>,
+    pub output_paths: Vec<String>,
+    pub output_dir: Option<String>,
+    pub checksums: Vec<Checksum>,
+    pub checksum_file: Option<String>,
+    pub decode_filename: bool,
+    /// Name files after the server's `Content-Disposition` header when it
+    /// has one (`--remote-header-name`).
+    pub remote_header_name: bool,
+    /// Normalize derived filenames to Unicode NFC (`--normalize-filename`).
+    pub normalize_filename: bool,
+    /// Rules derived filenames are made to follow (`--sanitize`).
+    pub sanitize: SanitizeProfile,
+    pub retries: u32,
+    /// Seconds between retries instead of the exponential backoff
+    /// (`--retry-delay`).
+    pub retry_delay: Option<u32>,
+    /// Seconds after which no more retries are started; 0 means no limit
+    /// (`--retry-max-time`).
+    pub retry_max_time: Option<u32>,
+    /// Retry every kind of failure, not just transient ones.
+    pub retry_all_errors: bool,
+    /// Retry connections the server refused.
+    pub retry_connrefused: bool,
+    pub parallel: bool,
+    /// Most transfers curl runs at once (`--parallel-max`), or curl's
+    /// default of 50.
+    pub parallel_max: Option<u32>,
+    /// Most connections to one host (`--parallel-max-host`), or 5 for
+    /// curls that support it.
+    pub parallel_max_host: Option<u32>,
+    pub resume: bool,
+    pub on_conflict: OnConflict,
+    pub on_collision: OnCollision,
+    /// Treat output names differing only in case as the same file.
+    pub ignore_case: bool,
+    pub dry_run: bool,
+    /// Where to write a JSON report of every transfer, or `-` for stdout.
+    pub report: Option<String>,
+    pub report_format: ReportFormat,
+    pub exit_code_mode: ExitCodeMode,
+    /// Path or name of the curl binary to run.
+    pub curl: String,
+    pub backend: Backend,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Config {
+            curl_options: Vec::new(),
+            urls: Vec::new(),
+            output_paths: Vec::new(),
+            output_dir: None,
+            checksums: Vec::new(),
+            checksum_file: None,
+            decode_filename: true,
+            remote_header_name: false,
+            normalize_filename: false,
+            sanitize: SanitizeProfile::native(),
+            retries: 5,
+            retry_delay: None,
+            retry_max_time: None,
+            retry_all_errors: false,
+            retry_connrefused: false,
+            parallel: true,
+            parallel_max: None,
+            parallel_max_host: None,
+            resume: false,
+            on_conflict: OnConflict::Rename,
+            on_collision: OnCollision::PrefixHost,
+            ignore_case: false,
+            dry_run: false,
+            report: None,
+            report_format: ReportFormat::Json,
+            exit_code_mode: ExitCodeMode::Curl,
+            curl: "curl".to_string(),
+            backend: Backend::Auto,
+        }
+    }
+
+    /// Applies the system and then the user configuration file, skipping
+    /// any that don't exist.
+    pub fn load_default_files(&mut self) -> Result<(), String> {
+        for path in default_config_paths() {
+            self.load_file(&path)?;
+        }
+        Ok(())
+    }
+
+    /// Applies the settings in the configuration file at `path`.
+    ///
+    /// Returns `Ok(false)` if the file does not exist.
+    pub fn load_file(&mut self, path: &Path) -> Result<bool, String> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+
+        let entries = toml::parse(&text)
+            .map_err(|(line, e)| format!("{}:{}: {}", path.display(), line, e))?;
+
+        for entry in entries {
+            self.apply_setting(&entry.key, entry.value)
+                .map_err(|e| format!("{}:{}: {}", path.display(), entry.line, e))?;
+        }
+
+        Ok(true)
+    }
+
+    fn apply_setting(&mut self, key: &str, value: Value) -> Result<(), String> {
+        let type_error = |expected: &str, value: &Value| {
+            Err(format!(
+                "'{}' must be {}, not {}",
+                key,
+                expected,
+                value.type_name()
+            ))
+        };
+
+        match (key, value) {
+            ("curl_options", Value::String(s)) => self.curl_options.extend(shell::split(&s)?),
+            ("curl_options", Value::Array(items)) => {
+                for item in items {
+                    match item {
+                        Value::String(s) => self.curl_options.push(s),
+                        other => return type_error("an array of strings", &other),
+                    }
+                }
+            }
+            ("curl_options", other) => {
+                return type_error("a string or an array of strings", &other)
+            }
+            ("output_dir", Value::String(s)) => self.output_dir = Some(s),
+            ("output_dir", other) => return type_error("a string", &other),
+            ("decode_filename", Value::Boolean(b)) => self.decode_filename = b,
+            ("decode_filename", other) => return type_error("a boolean", &other),
+            ("remote_header_name", Value::Boolean(b)) => self.remote_header_name = b,
+            ("remote_header_name", other) => return type_error("a boolean", &other),
+            ("normalize_filename", Value::Boolean(b)) => self.normalize_filename = b,
+            ("normalize_filename", other) => return type_error("a boolean", &other),
+            ("sanitize", Value::String(s)) => self.sanitize = s.parse()?,
+            ("sanitize", other) => return type_error("a string", &other),
+            ("parallel", Value::Boolean(b)) => self.parallel = b,
+            ("parallel", other) => return type_error("a boolean", &other),
+            ("retries", Value::Integer(n)) => self.retries = to_u32(key, n)?,
+            ("retries", other) => return type_error("an integer", &other),
+            ("retry_delay", Value::Integer(n)) => self.retry_delay = Some(to_u32(key, n)?),
+            ("retry_delay", other) => return type_error("an integer", &other),
+            ("retry_max_time", Value::Integer(n)) => self.retry_max_time = Some(to_u32(key, n)?),
+            ("retry_max_time", other) => return type_error("an integer", &other),
+            ("retry_all_errors", Value::Boolean(b)) => self.retry_all_errors = b,
+            ("retry_all_errors", other) => return type_error("a boolean", &other),
+            ("retry_connrefused", Value::Boolean(b)) => self.retry_connrefused = b,
+            ("retry_connrefused", other) => return type_error("a boolean", &other),
+            ("parallel_max", Value::Integer(n)) => self.parallel_max = Some(to_u32(key, n)?),
+            ("parallel_max", other) => return type_error("an integer", &other),
+            ("parallel_max_host", Value::Integer(n)) => {
+                self.parallel_max_host = Some(to_u32(key, n)?)
+            }
+            ("parallel_max_host", other) => return type_error("an integer", &other),
+            ("curl", Value::String(s)) => self.curl = s,
+            ("curl", other) => return type_error("a string", &other),
+            ("on_conflict", Value::String(s)) => self.on_conflict = s.parse()?,
+            ("on_conflict", other) => return type_error("a string", &other),
+            ("on_collision", Value::String(s)) => self.on_collision = s.parse()?,
+            ("on_collision", other) => return type_error("a string", &other),
+            ("ignore_case", Value::Boolean(b)) => self.ignore_case = b,
+            ("ignore_case", other) => return type_error("a boolean", &other),
+            ("exit_code_mode", Value::String(s)) => self.exit_code_mode = s.parse()?,
+            ("exit_code_mode", other) => return type_error("a string", &other),
+            ("backend", Value::String(s)) => self.backend = s.parse()?,
+            ("backend", other) => return type_error("a string", &other),
+            _ => return Err(format!("unknown setting '{}'", key)),
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::new()
+    }
+}
+
+fn to_u32(key: &str, n: i64) -> Result<u32, String> {
+    u32::try_from(n).map_err(|_| format!("'{}' must be a non-negative integer, not {}", key, n))
+}
+
+/// The configuration files wcurl reads, lowest precedence first:
+/// `/etc/wcurl/config.toml`, then `$XDG_CONFIG_HOME/wcurl/config.toml`
+/// (falling back to `~/.config`, or `%APPDATA%` on Windows).
+pub fn default_config_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+
+    if cfg!(unix) {
+        paths.push(PathBuf::from("/etc/wcurl/config.toml"));
+    }
+
+    let user_dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from));
+
+    if let Some(dir) = user_dir {
+        paths.push(dir.join("wcurl").join("config.toml"));
+    }
+
+    paths
+}
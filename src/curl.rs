@@ -0,0 +1,452 @@
+use std::fmt;
+use std::process::Command;
+use std::str::FromStr;
+
+/// A curl version such as `8.11.0` or `8.12.0-DEV`.
+///
+/// The pre-release suffix is kept for display but ignored when comparing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CurlVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    pub pre: Option<String>,
+}
+
+impl CurlVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        CurlVersion {
+            major,
+            minor,
+            patch,
+            pre: None,
+        }
+    }
+
+    fn triple(&self) -> (u32, u32, u32) {
+        (self.major, self.minor, self.patch)
+    }
+
+    pub fn at_least(&self, other: &CurlVersion) -> bool {
+        self.triple() >= other.triple()
+    }
+}
+
+impl FromStr for CurlVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (numbers, pre) = match s.split_once('-') {
+            Some((numbers, pre)) => (numbers, Some(pre.to_string())),
+            None => (s, None),
+        };
+
+        let mut parts = numbers.split('.');
+        let mut component = |name: &str, required: bool| match parts.next() {
+            Some(part) => part
+                .parse::<u32>()
+                .map_err(|_| format!("Invalid {} version in '{}'", name, s)),
+            None if required => Err(format!("Invalid version format: '{}'", s)),
+            None => Ok(0),
+        };
+
+        Ok(CurlVersion {
+            major: component("major", true)?,
+            minor: component("minor", true)?,
+            patch: component("patch", false)?,
+            pre,
+        })
+    }
+}
+
+impl fmt::Display for CurlVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if let Some(ref pre) = self.pre {
+            write!(f, "-{}", pre)?;
+        }
+        Ok(())
+    }
+}
+
+/// Something wcurl needs to know whether the installed curl can do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    Parallel,
+    ParallelMaxHost,
+    NoClobber,
+    OutputDir,
+    RemoveOnError,
+    Http3,
+    Metalink,
+    WriteOutExitCode,
+    WriteOutJson,
+    RetryConnrefused,
+    RetryAllErrors,
+}
+
+enum Requirement {
+    Version(CurlVersion),
+    Feature(&'static str),
+}
+
+/// What each capability requires: a minimum curl version, or a name in the
+/// `Features:` line of `curl --version`.
+static CAPABILITIES: [(Capability, &str, Requirement); 11] = [
+    (
+        Capability::Parallel,
+        "parallel",
+        Requirement::Version(CurlVersion::new(7, 66, 0)),
+    ),
+    (
+        Capability::ParallelMaxHost,
+        "parallel-max-host",
+        Requirement::Version(CurlVersion::new(8, 16, 0)),
+    ),
+    (
+        Capability::NoClobber,
+        "no-clobber",
+        Requirement::Version(CurlVersion::new(7, 83, 0)),
+    ),
+    (
+        Capability::OutputDir,
+        "output-dir",
+        Requirement::Version(CurlVersion::new(7, 73, 0)),
+    ),
+    (
+        Capability::RemoveOnError,
+        "remove-on-error",
+        Requirement::Version(CurlVersion::new(7, 83, 0)),
+    ),
+    (Capability::Http3, "http3", Requirement::Feature("HTTP3")),
+    (
+        Capability::Metalink,
+        "metalink",
+        Requirement::Feature("Metalink"),
+    ),
+    (
+        Capability::WriteOutExitCode,
+        "write-out-exitcode",
+        Requirement::Version(CurlVersion::new(7, 75, 0)),
+    ),
+    (
+        Capability::WriteOutJson,
+        "write-out-json",
+        Requirement::Version(CurlVersion::new(7, 70, 0)),
+    ),
+    (
+        Capability::RetryConnrefused,
+        "retry-connrefused",
+        Requirement::Version(CurlVersion::new(7, 52, 0)),
+    ),
+    (
+        Capability::RetryAllErrors,
+        "retry-all-errors",
+        Requirement::Version(CurlVersion::new(7, 71, 0)),
+    ),
+];
+
+impl Capability {
+    pub fn name(self) -> &'static str {
+        CAPABILITIES
+            .iter()
+            .find(|(cap, _, _)| *cap == self)
+            .map(|(_, name, _)| *name)
+            .unwrap()
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        CAPABILITIES
+            .iter()
+            .find(|(_, n, _)| *n == name)
+            .map(|(cap, _, _)| *cap)
+    }
+}
+
+/// Everything wcurl learns about curl from `curl --version`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CurlInfo {
+    /// The curl binary this describes, as it will be passed to `Command::new`.
+    pub program: String,
+    pub version: CurlVersion,
+    pub protocols: Vec<String>,
+    pub features: Vec<String>,
+}
+
+impl CurlInfo {
+    /// Describes a curl of the given version with no known protocols or features.
+    pub fn with_version(version: CurlVersion) -> Self {
+        CurlInfo {
+            program: "curl".to_string(),
+            version,
+            protocols: Vec::new(),
+            features: Vec::new(),
+        }
+    }
+
+    /// Runs `<program> --version` and parses its output, making sure
+    /// `program` is actually a working curl.
+    pub fn detect(program: &str) -> Result<Self, String> {
+        let output = Command::new(program)
+            .arg("--version")
+            .output()
+            .map_err(|e| format!("Failed to execute curl at '{}': {}", program, e))?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "'{} --version' exited with status: {}",
+                program, output.status
+            ));
+        }
+
+        let mut info = CurlInfo::parse(&String::from_utf8_lossy(&output.stdout))
+            .map_err(|e| format!("'{}' does not look like curl: {}", program, e))?;
+        info.program = program.to_string();
+        Ok(info)
+    }
+
+    /// Parses the output of `curl --version`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        let first_line = lines.next().ok_or("No version output")?;
+
+        let parts: Vec<&str> = first_line.split_whitespace().collect();
+        if parts.len() < 2 || parts[0] != "curl" {
+            return Err("Could not parse curl version".to_string());
+        }
+        let version = parts[1].parse()?;
+
+        let mut info = CurlInfo::with_version(version);
+        for line in lines {
+            let words = |rest: &str| rest.split_whitespace().map(str::to_string).collect();
+
+            if let Some(rest) = line.strip_prefix("Protocols:") {
+                info.protocols = words(rest);
+            } else if let Some(rest) = line.strip_prefix("Features:") {
+                info.features = words(rest);
+            }
+        }
+
+        Ok(info)
+    }
+
+    pub fn supports(&self, capability: Capability) -> bool {
+        let (_, _, requirement) = CAPABILITIES
+            .iter()
+            .find(|(cap, _, _)| *cap == capability)
+            .unwrap();
+
+        match requirement {
+            Requirement::Version(min) => self.version.at_least(min),
+            Requirement::Feature(name) => self.has_feature(name),
+        }
+    }
+
+    pub fn has_feature(&self, name: &str) -> bool {
+        self.features.iter().any(|f| f.eq_ignore_ascii_case(name))
+    }
+
+    pub fn has_protocol(&self, name: &str) -> bool {
+        self.protocols.iter().any(|p| p.eq_ignore_ascii_case(name))
+    }
+}
+
+/// What curl's exit codes mean, from the libcurl error list.
+static EXIT_CODES: [(u32, &str); 86] = [
+    (1, "Unsupported protocol"),
+    (2, "Failed to initialize"),
+    (3, "The URL is malformed"),
+    (
+        4,
+        "A feature or option that was needed is not available in this curl build",
+    ),
+    (5, "Couldn't resolve the proxy host"),
+    (6, "Couldn't resolve the host name"),
+    (7, "Failed to connect to the host"),
+    (8, "The server sent a reply curl couldn't parse"),
+    (9, "Access to the remote resource was denied"),
+    (
+        10,
+        "The FTP server failed to connect back for an active transfer",
+    ),
+    (
+        11,
+        "The FTP server sent an unexpected reply to the password",
+    ),
+    (12, "Timed out waiting for the FTP server to connect back"),
+    (13, "The FTP server sent an unexpected reply to PASV"),
+    (14, "The FTP server sent an unexpected 227 reply"),
+    (15, "Couldn't resolve the host given by the FTP server"),
+    (16, "A problem was detected in the HTTP/2 framing layer"),
+    (17, "Couldn't set the FTP transfer mode to binary"),
+    (18, "The transfer ended before all the data arrived"),
+    (19, "The FTP server refused to send the file"),
+    (21, "An FTP quote command failed"),
+    (
+        22,
+        "The server returned an HTTP error (400 or above), such as 404 Not Found",
+    ),
+    (23, "Couldn't write the downloaded data to the local file"),
+    (25, "The upload failed"),
+    (26, "Couldn't read the local file"),
+    (27, "Out of memory"),
+    (28, "The operation timed out"),
+    (30, "The FTP PORT command failed"),
+    (31, "The FTP REST command failed"),
+    (
+        33,
+        "The server doesn't support or rejected the requested byte range",
+    ),
+    (34, "The HTTP POST request failed"),
+    (35, "The SSL/TLS handshake failed"),
+    (36, "Couldn't resume the earlier download"),
+    (37, "Couldn't read the file given with a file:// URL"),
+    (38, "Couldn't bind to the LDAP server"),
+    (39, "The LDAP search failed"),
+    (41, "A required LDAP function wasn't found"),
+    (42, "The transfer was aborted by a callback"),
+    (43, "A libcurl function was called with a bad argument"),
+    (
+        45,
+        "The requested outgoing network interface couldn't be used",
+    ),
+    (47, "Too many redirects were followed"),
+    (48, "An unknown option was passed to libcurl"),
+    (49, "A telnet option was malformed"),
+    (52, "The server closed the connection without replying"),
+    (53, "The requested SSL crypto engine wasn't found"),
+    (54, "Couldn't make the SSL crypto engine the default"),
+    (55, "Failed to send network data"),
+    (56, "Failed to receive network data"),
+    (58, "There is a problem with the local client certificate"),
+    (59, "The requested SSL cipher couldn't be used"),
+    (
+        60,
+        "The server's certificate couldn't be verified with the known CA certificates",
+    ),
+    (61, "The server used an unrecognised transfer encoding"),
+    (63, "The maximum file size was exceeded"),
+    (64, "The requested FTP SSL level failed"),
+    (65, "Resending the request data failed"),
+    (66, "Failed to initialise the SSL crypto engine"),
+    (67, "The server didn't accept the login credentials"),
+    (68, "The file wasn't found on the TFTP server"),
+    (69, "Permission was denied by the TFTP server"),
+    (70, "The TFTP server is out of disk space"),
+    (71, "An illegal TFTP operation was attempted"),
+    (72, "The TFTP transfer ID is unknown"),
+    (73, "The file already exists on the TFTP server"),
+    (74, "The TFTP user doesn't exist"),
+    (77, "Couldn't read the SSL CA certificate file"),
+    (78, "The resource in the URL doesn't exist"),
+    (79, "An error occurred in the SSH session"),
+    (80, "Failed to shut down the SSL connection"),
+    (82, "Couldn't load the certificate revocation list"),
+    (83, "The certificate issuer check failed"),
+    (84, "The FTP PRET command failed"),
+    (85, "The RTSP CSeq numbers didn't match"),
+    (86, "The RTSP session identifiers didn't match"),
+    (87, "Couldn't parse the FTP file listing"),
+    (88, "An FTP chunk callback reported an error"),
+    (89, "No connection was available"),
+    (90, "The server's public key didn't match the pinned key"),
+    (91, "The server's certificate status was invalid"),
+    (92, "A stream error occurred in the HTTP/2 framing layer"),
+    (93, "A libcurl function was called from inside a callback"),
+    (94, "An authentication function returned an error"),
+    (95, "A problem was detected in the HTTP/3 layer"),
+    (96, "The QUIC connection failed"),
+    (97, "The proxy handshake failed"),
+    (98, "The server requires a client certificate"),
+    (99, "A fatal error occurred while waiting on the network"),
+    (100, "A value or data field was larger than allowed"),
+];
+
+/// Describes a curl exit code in plain words, if it is a known one.
+pub fn explain_exit_code(code: u32) -> Option<&'static str> {
+    EXIT_CODES
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map(|(_, text)| *text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VERSION_OUTPUT: &str = "\
+curl 8.12.0-DEV (x86_64-pc-linux-gnu) libcurl/8.12.0-DEV OpenSSL/3.0.13 zlib/1.3
+Release-Date: [unreleased]
+Protocols: dict file ftp ftps http https mqtt
+Features: alt-svc AsynchDNS HSTS HTTP2 HTTPS-proxy IPv6 Largefile libz SSL
+";
+
+    fn curl(version: &str) -> CurlInfo {
+        CurlInfo::with_version(version.parse().unwrap())
+    }
+
+    #[test]
+    fn parses_versions() {
+        assert_eq!(
+            "8.12.0-DEV".parse::<CurlVersion>(),
+            Ok(CurlVersion {
+                major: 8,
+                minor: 12,
+                patch: 0,
+                pre: Some("DEV".to_string()),
+            })
+        );
+        assert_eq!(
+            "7.88".parse::<CurlVersion>(),
+            Ok(CurlVersion::new(7, 88, 0))
+        );
+        assert_eq!(
+            "9.0.0".parse::<CurlVersion>(),
+            Ok(CurlVersion::new(9, 0, 0))
+        );
+        assert_eq!(CurlVersion::new(8, 12, 0).to_string(), "8.12.0");
+        assert_eq!(curl("8.12.0-DEV").version.to_string(), "8.12.0-DEV");
+    }
+
+    #[test]
+    fn rejects_bad_versions() {
+        for version in ["8", "", "8.x.0", "v8.1.0"] {
+            assert!(version.parse::<CurlVersion>().is_err(), "{}", version);
+        }
+    }
+
+    #[test]
+    fn parses_version_output() {
+        let info = CurlInfo::parse(VERSION_OUTPUT).unwrap();
+        assert_eq!(info.version.to_string(), "8.12.0-DEV");
+        assert_eq!(info.protocols.len(), 7);
+        assert!(info.has_protocol("HTTPS"));
+        assert!(!info.has_protocol("gopher"));
+        assert!(info.has_feature("http2"));
+        assert!(!info.supports(Capability::Http3));
+        assert!(!info.supports(Capability::ParallelMaxHost));
+        assert!(info.supports(Capability::WriteOutExitCode));
+
+        assert!(CurlInfo::parse("").is_err());
+        assert!(CurlInfo::parse("wget 1.21.4").is_err());
+    }
+
+    #[test]
+    fn versions_compare_as_a_whole() {
+        assert!(curl("9.0.0").supports(Capability::ParallelMaxHost));
+        assert!(curl("8.16.0").supports(Capability::ParallelMaxHost));
+        assert!(!curl("8.15.9").supports(Capability::ParallelMaxHost));
+        assert!(!curl("7.99.0").supports(Capability::ParallelMaxHost));
+        assert!(curl("7.66").supports(Capability::Parallel));
+        assert!(!curl("7.65.3").supports(Capability::Parallel));
+    }
+
+    #[test]
+    fn capability_names_round_trip() {
+        for (capability, name, _) in &CAPABILITIES {
+            assert_eq!(capability.name(), *name);
+            assert_eq!(Capability::from_name(name), Some(*capability));
+        }
+        assert_eq!(Capability::from_name("teleport"), None);
+    }
+}
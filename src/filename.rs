@@ -0,0 +1,563 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Longest filename, in bytes, most filesystems accept.
+pub const MAX_FILENAME_BYTES: usize = 255;
+
+/// Longest suffix kept as an extension when a name has to be truncated.
+const MAX_EXTENSION_BYTES: usize = 32;
+
+/// Names Windows reserves for devices, with or without an extension.
+const WINDOWS_RESERVED: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Which filesystems a derived filename has to be valid on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SanitizeProfile {
+    /// Only control characters are replaced.
+    Posix,
+    /// Also replaces `<>:"|?*`, trailing dots and spaces, and renames
+    /// reserved device names such as `CON` or `NUL.txt`.
+    Windows,
+    /// The Windows rules, and only letters, digits, `.`, `_` and `-`.
+    Portable,
+}
+
+impl SanitizeProfile {
+    /// The profile for the platform wcurl was built for.
+    pub fn native() -> Self {
+        if cfg!(windows) {
+            SanitizeProfile::Windows
+        } else {
+            SanitizeProfile::Posix
+        }
+    }
+}
+
+impl FromStr for SanitizeProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "posix" => Ok(SanitizeProfile::Posix),
+            "windows" => Ok(SanitizeProfile::Windows),
+            "portable" => Ok(SanitizeProfile::Portable),
+            _ => Err(format!(
+                "Unknown filename profile '{}': expected posix, windows or portable",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SanitizeProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SanitizeProfile::Posix => "posix",
+            SanitizeProfile::Windows => "windows",
+            SanitizeProfile::Portable => "portable",
+        })
+    }
+}
+
+/// Replaces spaces in a URL with `%20`, as the original wcurl does.
+pub fn encode_whitespace(url: &str) -> String {
+    url.replace(' ', "%20")
+}
+
+/// Derives the output filename from the last path segment of `url`.
+///
+/// Falls back to `index.html` when the URL has no filename component.
+pub fn get_url_filename(url: &str, decode: bool) -> String {
+    let url_path = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+
+    let path_no_query = url_path.split(&['?', '#'][..]).next().unwrap_or(url_path);
+
+    let filename = path_no_query.rsplit('/').next().unwrap_or("");
+
+    if filename.is_empty() {
+        return "index.html".to_string();
+    }
+
+    if decode {
+        percent_decode(filename)
+    } else {
+        filename.to_string()
+    }
+}
+
+/// Percent-decodes `s` as UTF-8, leaving control characters and path
+/// separators encoded. Escapes that don't form valid UTF-8 are kept as
+/// their original `%XX` text.
+pub fn percent_decode(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while !rest.is_empty() {
+        // Collect a run of decodable escapes so multi-byte characters that
+        // span several of them are decoded together.
+        let run = rest;
+        let mut bytes = Vec::new();
+        while let Some(byte) = decodable_escape(rest) {
+            bytes.push(byte);
+            rest = &rest[3..];
+        }
+
+        if bytes.is_empty() {
+            let c = rest.chars().next().unwrap();
+            result.push(c);
+            rest = &rest[c.len_utf8()..];
+        } else {
+            push_utf8_or_escapes(&mut result, &bytes, run);
+        }
+    }
+
+    result
+}
+
+/// Returns the byte `s` starts with an escape for, if it may be decoded.
+fn decodable_escape(s: &str) -> Option<u8> {
+    let hex = s.strip_prefix('%')?.get(..2)?;
+    let byte = u8::from_str_radix(hex, 16).ok()?;
+    (byte >= 0x20 && byte != 0x7F && !is_unsafe_char(byte)).then_some(byte)
+}
+
+/// Appends `bytes` decoded as UTF-8. Bytes that aren't valid UTF-8 are
+/// copied from `escapes`, the `%XX` text they were decoded from.
+fn push_utf8_or_escapes(result: &mut String, bytes: &[u8], escapes: &str) {
+    let mut start = 0;
+
+    while start < bytes.len() {
+        match std::str::from_utf8(&bytes[start..]) {
+            Ok(valid) => {
+                result.push_str(valid);
+                return;
+            }
+            Err(e) => {
+                let valid_end = start + e.valid_up_to();
+                result.push_str(std::str::from_utf8(&bytes[start..valid_end]).unwrap());
+                let invalid_end = valid_end + e.error_len().unwrap_or(bytes.len() - valid_end);
+                result.push_str(&escapes[valid_end * 3..invalid_end * 3]);
+                start = invalid_end;
+            }
+        }
+    }
+}
+
+/// Converts `name` to Unicode Normalization Form C, so the same name sent
+/// composed by one server and decomposed by another compares equal.
+#[cfg(feature = "nfc")]
+pub fn normalize_filename(name: &str) -> String {
+    use unicode_normalization::UnicodeNormalization;
+
+    name.nfc().collect()
+}
+
+/// Without the `nfc` feature names are returned unchanged.
+#[cfg(not(feature = "nfc"))]
+pub fn normalize_filename(name: &str) -> String {
+    name.to_string()
+}
+
+/// Returns true for bytes that must never be decoded into a filename (`/` and `\`).
+pub fn is_unsafe_char(byte: u8) -> bool {
+    byte == 0x2F || byte == 0x5C
+}
+
+/// Extracts the filename from a `Content-Disposition` header value,
+/// preferring the RFC 5987 `filename*=charset''...` form over `filename`.
+pub fn content_disposition_filename(value: &str) -> Option<String> {
+    let mut plain = None;
+    let mut extended = None;
+
+    for param in split_params(value).into_iter().skip(1) {
+        let (name, val) = match param.split_once('=') {
+            Some((name, val)) => (name.trim().to_ascii_lowercase(), val.trim()),
+            None => continue,
+        };
+        match name.as_str() {
+            "filename*" => extended = decode_ext_value(val),
+            "filename" => plain = Some(unquote(val)),
+            _ => {}
+        }
+    }
+
+    extended.or(plain)
+}
+
+/// Checks a filename suggested by the server, returning it only if it is
+/// safe to create inside the output directory: no path separators (see
+/// [`is_unsafe_char`]), no control characters, not absolute and not `.`
+/// or `..`.
+pub fn sanitize_remote_filename(name: &str) -> Option<String> {
+    let name = name.trim();
+
+    let unsafe_char = name
+        .chars()
+        .any(|c| c.is_control() || (c.is_ascii() && is_unsafe_char(c as u8)));
+    let drive = name.len() >= 2 && name.as_bytes()[1] == b':';
+
+    if name.is_empty() || name == "." || name == ".." || unsafe_char || drive {
+        return None;
+    }
+
+    Some(name.to_string())
+}
+
+/// Splits a header value on `;`, ignoring any inside quoted strings.
+fn split_params(value: &str) -> Vec<String> {
+    let mut params = Vec::new();
+    let mut current = String::new();
+    let mut chars = value.chars();
+    let mut quoted = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' if quoted => {
+                current.push(c);
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+                continue;
+            }
+            ';' if !quoted => {
+                params.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    params.push(current);
+
+    params
+}
+
+/// Removes the quotes and backslash escapes of an HTTP quoted-string, or
+/// returns a token as is.
+fn unquote(value: &str) -> String {
+    let inner = match value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    {
+        Some(inner) => inner,
+        None => return value.to_string(),
+    };
+
+    let mut result = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => result.extend(chars.next()),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Decodes an RFC 5987 `charset'language'percent-encoded` value. Only
+/// UTF-8 and ISO-8859-1 are understood.
+fn decode_ext_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = parts.next()?;
+    let _language = parts.next()?;
+    let encoded = parts.next()?;
+
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut iter = encoded.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+
+    if charset.eq_ignore_ascii_case("utf-8") {
+        String::from_utf8(bytes).ok()
+    } else if charset.eq_ignore_ascii_case("iso-8859-1") {
+        Some(bytes.into_iter().map(char::from).collect())
+    } else {
+        None
+    }
+}
+
+/// Makes a derived filename valid under `profile`. Disallowed characters
+/// become `_`, and names longer than 255 bytes are shortened while keeping
+/// their extension, so the same input always gives the same name.
+pub fn sanitize_filename(name: &str, profile: SanitizeProfile) -> String {
+    let windows = profile != SanitizeProfile::Posix;
+
+    let mut result: String = name
+        .chars()
+        .map(|c| {
+            let allowed = match profile {
+                SanitizeProfile::Posix => !c.is_control() && c != '/',
+                SanitizeProfile::Windows => {
+                    !c.is_control()
+                        && !matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*')
+                }
+                SanitizeProfile::Portable => {
+                    c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-')
+                }
+            };
+            if allowed {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    if windows {
+        result = trim_trailing_dots(&result);
+        let stem = result.split('.').next().unwrap_or("");
+        if WINDOWS_RESERVED
+            .iter()
+            .any(|reserved| stem.eq_ignore_ascii_case(reserved))
+        {
+            result.insert(0, '_');
+        }
+    }
+    if profile == SanitizeProfile::Portable && result.starts_with('-') {
+        result.replace_range(..1, "_");
+    }
+
+    result = truncate_filename(&result, MAX_FILENAME_BYTES);
+    if windows {
+        result = trim_trailing_dots(&result);
+    }
+
+    match result.as_str() {
+        "" => "_".to_string(),
+        "." => "_".to_string(),
+        ".." => "__".to_string(),
+        _ => result,
+    }
+}
+
+/// Windows silently drops trailing dots and spaces from names.
+fn trim_trailing_dots(name: &str) -> String {
+    name.trim_end_matches(['.', ' ']).to_string()
+}
+
+/// Shortens `name` to at most `max` bytes on a character boundary, cutting
+/// from the part before the extension when there is one.
+pub fn truncate_filename(name: &str, max: usize) -> String {
+    if name.len() <= max {
+        return name.to_string();
+    }
+
+    let (stem, extension) = match name.rfind('.') {
+        Some(dot) if dot > 0 && name.len() - dot <= MAX_EXTENSION_BYTES => name.split_at(dot),
+        _ => (name, ""),
+    };
+
+    format!(
+        "{}{}",
+        floor_char_boundary(stem, max - extension.len()),
+        extension
+    )
+}
+
+/// `name (n).ext` for `name.ext`, shortening the part before the extension
+/// so the result still fits in [`MAX_FILENAME_BYTES`]. Dotfiles and names
+/// without an extension get the number at the end.
+pub fn numbered_filename(name: &str, n: u32) -> String {
+    let (stem, extension) = match name.rfind('.') {
+        Some(dot) if dot > 0 => name.split_at(dot),
+        _ => (name, ""),
+    };
+    let number = format!(" ({})", n);
+    let room = MAX_FILENAME_BYTES.saturating_sub(number.len() + extension.len());
+    format!("{}{}{}", floor_char_boundary(stem, room), number, extension)
+}
+
+/// The longest start of `s` that is at most `max` bytes and ends on a
+/// character boundary.
+fn floor_char_boundary(s: &str, max: usize) -> &str {
+    if s.len() <= max {
+        return s;
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_filename_keeps_escaped_quotes_and_semicolons() {
+        assert_eq!(
+            content_disposition_filename(r#"attachment; filename="a\"b;c.txt"; size=3"#),
+            Some(r#"a"b;c.txt"#.to_string())
+        );
+        assert_eq!(
+            content_disposition_filename("attachment; filename=plain.txt"),
+            Some("plain.txt".to_string())
+        );
+        assert_eq!(content_disposition_filename("inline"), None);
+    }
+
+    #[test]
+    fn extended_filename_is_preferred() {
+        assert_eq!(
+            content_disposition_filename(
+                "attachment; filename*=UTF-8''%C3%A9t%C3%A9.txt; filename=\"ete.txt\""
+            ),
+            Some("été.txt".to_string())
+        );
+        assert_eq!(
+            content_disposition_filename(
+                "attachment; filename=\"ete.txt\"; FILENAME*=iso-8859-1'en'%E9t%E9.txt"
+            ),
+            Some("été.txt".to_string())
+        );
+    }
+
+    #[test]
+    fn undecodable_extended_filename_falls_back() {
+        assert_eq!(
+            content_disposition_filename(
+                "attachment; filename=\"ete.txt\"; filename*=UTF-8''%C3%28.txt"
+            ),
+            Some("ete.txt".to_string())
+        );
+        assert_eq!(decode_ext_value("UTF-8''%4"), None);
+        assert_eq!(decode_ext_value("KOI8-R''%C1"), None);
+        assert_eq!(decode_ext_value("no-quotes"), None);
+    }
+
+    #[test]
+    fn remote_filenames_stay_in_the_output_directory() {
+        assert_eq!(
+            sanitize_remote_filename(" report.pdf "),
+            Some("report.pdf".to_string())
+        );
+        assert_eq!(
+            sanitize_remote_filename("..hidden"),
+            Some("..hidden".to_string())
+        );
+        for name in [
+            "", ".", "..", "/etc/x", "../x", "a/b", "a\\b", "C:x", "a\0b", "a\nb", "a\u{7f}",
+        ] {
+            assert_eq!(sanitize_remote_filename(name), None, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn posix_only_replaces_control_characters_and_slashes() {
+        let posix = SanitizeProfile::Posix;
+        assert_eq!(sanitize_filename("a:b?*.txt", posix), "a:b?*.txt");
+        assert_eq!(sanitize_filename("a\tb/c", posix), "a_b_c");
+        assert_eq!(sanitize_filename("NUL.txt", posix), "NUL.txt");
+        assert_eq!(sanitize_filename("name. ", posix), "name. ");
+        assert_eq!(sanitize_filename("..", posix), "__");
+    }
+
+    #[test]
+    fn windows_rules() {
+        let windows = SanitizeProfile::Windows;
+        assert_eq!(
+            sanitize_filename("a<b>:\"c|?*.txt", windows),
+            "a_b___c___.txt"
+        );
+        assert_eq!(sanitize_filename("NUL.txt", windows), "_NUL.txt");
+        assert_eq!(sanitize_filename("com1", windows), "_com1");
+        assert_eq!(sanitize_filename("console.txt", windows), "console.txt");
+        assert_eq!(sanitize_filename("name. . ", windows), "name");
+        assert_eq!(sanitize_filename("...", windows), "_");
+    }
+
+    #[test]
+    fn portable_rules() {
+        let portable = SanitizeProfile::Portable;
+        assert_eq!(sanitize_filename("-rf", portable), "_rf");
+        assert_eq!(
+            sanitize_filename("my file (1).tar.gz", portable),
+            "my_file__1_.tar.gz"
+        );
+        assert_eq!(sanitize_filename("été.txt", portable), "_t_.txt");
+        assert_eq!(sanitize_filename("NUL.txt", portable), "_NUL.txt");
+        assert_eq!(sanitize_filename("name..", portable), "name");
+    }
+
+    #[test]
+    fn long_names_keep_their_extension() {
+        let name = format!("{}.txt", "é".repeat(150));
+        for profile in [SanitizeProfile::Posix, SanitizeProfile::Windows] {
+            let sanitized = sanitize_filename(&name, profile);
+            assert_eq!(sanitized.len(), 254);
+            assert!(sanitized.ends_with("é.txt"));
+            assert_eq!(sanitized, sanitize_filename(&name, profile));
+        }
+
+        let long_extension = format!("a.{}", "x".repeat(300));
+        assert_eq!(
+            truncate_filename(&long_extension, 255),
+            long_extension[..255]
+        );
+        assert_eq!(truncate_filename("short.txt", 255), "short.txt");
+    }
+
+    #[test]
+    fn percent_decodes_utf8() {
+        assert_eq!(percent_decode("%C3%A9t%C3%A9.txt"), "été.txt");
+        assert_eq!(percent_decode("a%20b%2b"), "a b+");
+        assert_eq!(percent_decode("%E2%82%AC"), "€");
+    }
+
+    #[test]
+    fn invalid_utf8_keeps_its_escapes() {
+        assert_eq!(percent_decode("%C3%28"), "%C3(");
+        assert_eq!(percent_decode("%FFa%C3%A9"), "%FFaé");
+        assert_eq!(percent_decode("%E2%82"), "%E2%82");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz"), "%zz");
+    }
+
+    #[test]
+    fn separators_and_control_characters_stay_encoded() {
+        for escape in ["%2F", "%2f", "%5C", "%00", "%1F", "%7F"] {
+            assert_eq!(percent_decode(escape), escape);
+        }
+        assert_eq!(
+            percent_decode("..%2F..%2Fetc%2Fpasswd"),
+            "..%2F..%2Fetc%2Fpasswd"
+        );
+    }
+
+    #[test]
+    fn url_filenames_are_decoded_on_request() {
+        assert_eq!(
+            get_url_filename("https://h/dir/caf%C3%A9.txt?x=1#y", true),
+            "café.txt"
+        );
+        assert_eq!(
+            get_url_filename("https://h/dir/caf%C3%A9.txt", false),
+            "caf%C3%A9.txt"
+        );
+        assert_eq!(get_url_filename("https://h/dir/", true), "index.html");
+    }
+
+    #[test]
+    fn numbered_names_fit() {
+        assert_eq!(numbered_filename("a.tar.gz", 1), "a.tar (1).gz");
+        assert_eq!(numbered_filename(".bashrc", 2), ".bashrc (2)");
+        assert_eq!(numbered_filename("README", 10), "README (10)");
+
+        let name = format!("{}.txt", "é".repeat(125));
+        let numbered = numbered_filename(&name, 1);
+        assert!(numbered.len() <= MAX_FILENAME_BYTES);
+        assert!(numbered.ends_with("é (1).txt"));
+    }
+}
@@ -0,0 +1,536 @@
+//! A minimal HTTP/1.1 client used by the native download backend.
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::VERSION;
+
+/// How long to wait for a connection, curl's default connect timeout.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How long a connection may go without sending or receiving anything
+/// before it counts as timed out and may be retried.
+const IO_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A parsed `http` or `https` URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Url {
+    pub scheme: String,
+    pub userinfo: Option<String>,
+    pub host: String,
+    pub port: u16,
+    /// Path and query, always starting with `/`.
+    pub path: String,
+}
+
+impl Url {
+    /// Parses `url`, assuming `https` when it has no scheme (like curl's
+    /// `--proto-default https`). Fragments are dropped.
+    pub fn parse(url: &str) -> Result<Url, String> {
+        let (scheme, rest) = match url.split_once("://") {
+            Some((scheme, rest)) => (scheme.to_ascii_lowercase(), rest),
+            None => ("https".to_string(), url),
+        };
+
+        let default_port = match scheme.as_str() {
+            "http" => 80,
+            "https" => 443,
+            _ => return Err(format!("Protocol \"{}\" not supported", scheme)),
+        };
+
+        let rest = rest.split('#').next().unwrap_or(rest);
+        let authority_end = rest.find(['/', '?']).unwrap_or(rest.len());
+        let (authority, path) = rest.split_at(authority_end);
+
+        let (userinfo, hostport) = match authority.rsplit_once('@') {
+            Some((userinfo, hostport)) => (Some(userinfo.to_string()), hostport),
+            None => (None, authority),
+        };
+
+        let (host, port) = if let Some(bracketed) = hostport.strip_prefix('[') {
+            let (host, after) = bracketed
+                .split_once(']')
+                .ok_or(format!("Malformed IPv6 address in URL: {}", url))?;
+            (host, after.strip_prefix(':'))
+        } else {
+            match hostport.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (hostport, None),
+            }
+        };
+
+        if host.is_empty() {
+            return Err(format!("No host part in the URL: {}", url));
+        }
+
+        let port = match port {
+            Some("") | None => default_port,
+            Some(port) => port
+                .parse()
+                .map_err(|_| format!("Invalid port in URL: {}", url))?,
+        };
+
+        let path = if path.starts_with('/') {
+            path.to_string()
+        } else {
+            format!("/{}", path)
+        };
+
+        Ok(Url {
+            scheme,
+            userinfo,
+            host: host.to_string(),
+            port,
+            path,
+        })
+    }
+
+    /// Resolves a `Location` header value against this URL.
+    pub fn join(&self, location: &str) -> Result<Url, String> {
+        if location.contains("://") {
+            return Url::parse(location);
+        }
+        if let Some(rest) = location.strip_prefix("//") {
+            return Url::parse(&format!("{}://{}", self.scheme, rest));
+        }
+
+        let path = if location.starts_with('/') {
+            location.to_string()
+        } else if location.starts_with('?') {
+            let base = self.path.split('?').next().unwrap_or("/");
+            format!("{}{}", base, location)
+        } else {
+            let base = self.path.split('?').next().unwrap_or("/");
+            let dir = &base[..base.rfind('/').map_or(0, |i| i + 1)];
+            format!("{}{}", dir, location)
+        };
+
+        Ok(Url {
+            userinfo: None,
+            path: path.split('#').next().unwrap_or("/").to_string(),
+            ..self.clone()
+        })
+    }
+
+    fn host_header(&self) -> String {
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+
+        match (self.scheme.as_str(), self.port) {
+            ("http", 80) | ("https", 443) => host,
+            _ => format!("{}:{}", host, self.port),
+        }
+    }
+}
+
+impl fmt::Display for Url {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://{}{}", self.scheme, self.host_header(), self.path)
+    }
+}
+
+/// A failed request. Transient errors are worth retrying.
+#[derive(Debug, Clone)]
+pub struct Error {
+    pub message: String,
+    pub transient: bool,
+    /// The HTTP status, for errors caused by one.
+    pub status: Option<u16>,
+    /// How long the server asked us to wait before retrying, if it said.
+    pub retry_after: Option<Duration>,
+    /// Whether the server refused the connection, which is only retried
+    /// with `--retry-connrefused`.
+    pub connection_refused: bool,
+}
+
+impl Error {
+    pub fn fatal<S: Into<String>>(message: S) -> Self {
+        Error {
+            message: message.into(),
+            transient: false,
+            status: None,
+            retry_after: None,
+            connection_refused: false,
+        }
+    }
+
+    /// Wraps an I/O error. Timeouts are transient, as with curl's
+    /// `--retry`, and so are connections dropped mid-transfer, which wcurl
+    /// retries for curl too.
+    pub fn io<S: Into<String>>(message: S, e: &io::Error) -> Self {
+        Error {
+            message: message.into(),
+            transient: matches!(
+                e.kind(),
+                io::ErrorKind::TimedOut
+                    | io::ErrorKind::WouldBlock
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::UnexpectedEof
+            ),
+            status: None,
+            retry_after: None,
+            connection_refused: e.kind() == io::ErrorKind::ConnectionRefused,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+trait Stream: Read + Write {}
+impl<T: Read + Write> Stream for T {}
+
+enum Body {
+    Length(u64),
+    Chunked { remaining: u64, done: bool },
+    UntilClose,
+}
+
+/// A response whose headers have been read; the body is read through [`Read`].
+pub struct Response {
+    /// The URL this is the response to.
+    pub url: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    reader: BufReader<Box<dyn Stream>>,
+    body: Body,
+}
+
+impl Response {
+    /// Returns the first header named `name`, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn read_chunk_size(&mut self) -> io::Result<u64> {
+        let mut line = String::new();
+        self.reader.read_line(&mut line)?;
+        let size = line.trim().split(';').next().unwrap_or("");
+        u64::from_str_radix(size.trim(), 16)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad chunk size"))
+    }
+}
+
+impl Read for Response {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.body {
+            Body::Length(0) => Ok(0),
+            Body::Length(remaining) => {
+                let max = remaining.min(buf.len() as u64) as usize;
+                let n = self.reader.read(&mut buf[..max])?;
+                if n == 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        format!("transfer closed with {} bytes remaining", remaining),
+                    ));
+                }
+                self.body = Body::Length(remaining - n as u64);
+                Ok(n)
+            }
+            Body::Chunked { done: true, .. } => Ok(0),
+            Body::Chunked { remaining: 0, .. } => {
+                let size = self.read_chunk_size()?;
+                if size == 0 {
+                    let mut line = String::new();
+                    while self.reader.read_line(&mut line)? > 2 {
+                        line.clear();
+                    }
+                    self.body = Body::Chunked {
+                        remaining: 0,
+                        done: true,
+                    };
+                    return Ok(0);
+                }
+                self.body = Body::Chunked {
+                    remaining: size,
+                    done: false,
+                };
+                self.read(buf)
+            }
+            Body::Chunked { remaining, .. } => {
+                let max = remaining.min(buf.len() as u64) as usize;
+                let n = self.reader.read(&mut buf[..max])?;
+                if n == 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "transfer closed in the middle of a chunk",
+                    ));
+                }
+                let remaining = remaining - n as u64;
+                if remaining == 0 {
+                    let mut crlf = [0u8; 2];
+                    self.reader.read_exact(&mut crlf)?;
+                }
+                self.body = Body::Chunked {
+                    remaining,
+                    done: false,
+                };
+                Ok(n)
+            }
+            Body::UntilClose => match self.reader.read(buf) {
+                // Servers often close TLS connections without a close_notify.
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(0),
+                other => other,
+            },
+        }
+    }
+}
+
+/// Describes an error reading from or writing to a connection, naming
+/// timeouts, which the OS reports as "Resource temporarily unavailable" on
+/// Unix, as such.
+pub fn describe(e: &io::Error) -> String {
+    match e.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => format!(
+            "Operation timed out after {} seconds without data",
+            IO_TIMEOUT.as_secs()
+        ),
+        _ => e.to_string(),
+    }
+}
+
+/// Opens a TCP connection to `url`'s host, trying each of its addresses in
+/// turn, with [`CONNECT_TIMEOUT`] and [`IO_TIMEOUT`] applied.
+fn connect(url: &Url) -> io::Result<TcpStream> {
+    let mut last_error = None;
+    for addr in (url.host.as_str(), url.port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(tcp) => {
+                tcp.set_read_timeout(Some(IO_TIMEOUT))?;
+                tcp.set_write_timeout(Some(IO_TIMEOUT))?;
+                return Ok(tcp);
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error
+        .unwrap_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no addresses found for host")))
+}
+
+/// Sends a `GET` request for `url` with the given extra headers.
+pub fn get(url: &Url, headers: &[(String, String)]) -> Result<Response, Error> {
+    let tcp = connect(url).map_err(|e| {
+        let message = format!("Failed to connect to {} port {}: {}", url.host, url.port, e);
+        Error::io(message, &e)
+    })?;
+
+    let stream: Box<dyn Stream> = match url.scheme.as_str() {
+        "https" => tls::connect(&url.host, tcp)?,
+        _ => Box::new(tcp),
+    };
+    let mut reader = BufReader::new(stream);
+
+    let mut request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nAccept: */*\r\nConnection: close\r\n",
+        url.path,
+        url.host_header()
+    );
+    let has_header = |name: &str| headers.iter().any(|(n, _)| n.eq_ignore_ascii_case(name));
+    if !has_header("User-Agent") {
+        request.push_str(&format!("User-Agent: wcurl/{}\r\n", VERSION));
+    }
+    if let Some(ref userinfo) = url.userinfo {
+        if !has_header("Authorization") {
+            let credentials = crate::filename::percent_decode(userinfo);
+            request.push_str(&format!(
+                "Authorization: Basic {}\r\n",
+                base64(credentials.as_bytes())
+            ));
+        }
+    }
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("\r\n");
+
+    let io_error = |e: io::Error| {
+        Error::io(
+            format!("Failed talking to {}: {}", url.host, describe(&e)),
+            &e,
+        )
+    };
+
+    reader
+        .get_mut()
+        .write_all(request.as_bytes())
+        .map_err(io_error)?;
+    reader.get_mut().flush().map_err(io_error)?;
+
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line).map_err(io_error)?;
+    let mut parts = status_line.split_whitespace();
+    let version = parts.next().unwrap_or("");
+    let status = parts
+        .next()
+        .and_then(|code| code.parse::<u16>().ok())
+        .filter(|_| version.starts_with("HTTP/"))
+        .ok_or_else(|| Error::fatal(format!("Invalid HTTP response from {}", url.host)))?;
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(io_error)? == 0 {
+            return Err(Error::fatal(format!(
+                "Connection to {} closed while reading headers",
+                url.host
+            )));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(n, _): &&(String, String)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    };
+    let body = if header("Transfer-Encoding").is_some_and(|te| te.eq_ignore_ascii_case("chunked")) {
+        Body::Chunked {
+            remaining: 0,
+            done: false,
+        }
+    } else if let Some(len) = header("Content-Length").and_then(|len| len.parse().ok()) {
+        Body::Length(len)
+    } else if status == 204 || status == 304 || (100..200).contains(&status) {
+        Body::Length(0)
+    } else {
+        Body::UntilClose
+    };
+
+    Ok(Response {
+        url: url.to_string(),
+        status,
+        headers,
+        reader,
+        body,
+    })
+}
+
+pub(crate) fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+/// Parses an HTTP date in the IMF-fixdate format, e.g.
+/// `Sun, 06 Nov 1994 08:49:37 GMT`.
+pub fn parse_http_date(s: &str) -> Option<SystemTime> {
+    let parts: Vec<&str> = s.split_whitespace().collect();
+    if parts.len() != 6 || parts[5] != "GMT" {
+        return None;
+    }
+
+    let day: i64 = parts[1].parse().ok()?;
+    let month = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ]
+    .iter()
+    .position(|m| *m == parts[2])? as i64
+        + 1;
+    let year: i64 = parts[3].parse().ok()?;
+
+    let mut hms = parts[4].split(':').map(|p| p.parse::<i64>().ok());
+    let (hour, min, sec) = (hms.next()??, hms.next()??, hms.next()??);
+
+    // Days since the epoch, from Howard Hinnant's days_from_civil.
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    let secs = days * 86400 + hour * 3600 + min * 60 + sec;
+    let secs = u64::try_from(secs).ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+#[cfg(feature = "tls")]
+mod tls {
+    use std::net::TcpStream;
+    use std::sync::{Arc, OnceLock};
+
+    use rustls::pki_types::ServerName;
+    use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+
+    use super::{Error, Stream};
+
+    fn config() -> Result<Arc<ClientConfig>, Error> {
+        static CONFIG: OnceLock<Result<Arc<ClientConfig>, String>> = OnceLock::new();
+
+        CONFIG
+            .get_or_init(|| {
+                let roots = RootCertStore {
+                    roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+                };
+                let provider = Arc::new(rustls::crypto::ring::default_provider());
+                ClientConfig::builder_with_provider(provider)
+                    .with_safe_default_protocol_versions()
+                    .map(|builder| {
+                        Arc::new(builder.with_root_certificates(roots).with_no_client_auth())
+                    })
+                    .map_err(|e| format!("Failed to set up TLS: {}", e))
+            })
+            .clone()
+            .map_err(Error::fatal)
+    }
+
+    pub(super) fn connect(host: &str, tcp: TcpStream) -> Result<Box<dyn Stream>, Error> {
+        let name = ServerName::try_from(host.to_string())
+            .map_err(|_| Error::fatal(format!("Invalid TLS server name: {}", host)))?;
+        let conn = ClientConnection::new(config()?, name)
+            .map_err(|e| Error::fatal(format!("TLS error for {}: {}", host, e)))?;
+
+        Ok(Box::new(StreamOwned::new(conn, tcp)))
+    }
+}
+
+#[cfg(not(feature = "tls"))]
+mod tls {
+    use std::net::TcpStream;
+
+    use super::{Error, Stream};
+
+    pub(super) fn connect(_host: &str, _tcp: TcpStream) -> Result<Box<dyn Stream>, Error> {
+        Err(Error::fatal(
+            "HTTPS is not supported: wcurl was built without the \"tls\" feature",
+        ))
+    }
+}
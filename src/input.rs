@@ -0,0 +1,45 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+
+use crate::filename::encode_whitespace;
+
+/// Reads URLs from `path`, or from standard input when `path` is `-`.
+pub fn read_url_file(path: &str) -> Result<Vec<String>, String> {
+    if path == "-" {
+        let stdin = io::stdin();
+        read_urls(stdin.lock(), "<stdin>")
+    } else {
+        let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+        read_urls(BufReader::new(file), path)
+    }
+}
+
+/// Reads one URL per line, skipping blank lines and `#` comments.
+///
+/// `source` is only used to label errors, which also carry the line number.
+pub fn read_urls<R: BufRead>(reader: R, source: &str) -> Result<Vec<String>, String> {
+    let mut urls = Vec::new();
+
+    for (idx, line) in reader.split(b'\n').enumerate() {
+        let line_no = idx + 1;
+        let bytes = line.map_err(|e| format!("Failed to read {}: {}", source, e))?;
+        let line = String::from_utf8(bytes)
+            .map_err(|_| format!("{}:{}: URL is not valid UTF-8", source, line_no))?;
+
+        let url = line.trim();
+        if url.is_empty() || url.starts_with('#') {
+            continue;
+        }
+
+        if url.chars().any(|c| c.is_control()) {
+            return Err(format!(
+                "{}:{}: URL contains control characters: {:?}",
+                source, line_no, url
+            ));
+        }
+
+        urls.push(encode_whitespace(url));
+    }
+
+    Ok(urls)
+}
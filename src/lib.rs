@@ -0,0 +1,317 @@
+use std::fmt;
+use std::fs;
+use std::thread;
+
+mod checksum;
+mod config;
+mod curl;
+pub mod digest;
+mod filename;
+mod http;
+mod input;
+mod json;
+mod manifest;
+mod native;
+mod plan;
+mod report;
+mod resume;
+mod retry;
+pub mod shell;
+mod staging;
+mod toml;
+
+pub use checksum::Checksum;
+pub use config::{default_config_paths, Backend, Config, ExitCodeMode, OnCollision, OnConflict};
+pub use curl::{explain_exit_code, Capability, CurlInfo, CurlVersion};
+pub use filename::{
+    content_disposition_filename, encode_whitespace, get_url_filename, is_unsafe_char,
+    normalize_filename, percent_decode, sanitize_filename, sanitize_remote_filename,
+    SanitizeProfile,
+};
+pub use input::{read_url_file, read_urls};
+pub use manifest::ChecksumManifest;
+pub use native::NativeOptions;
+pub use plan::{CurlInvocation, Download, DownloadPlan, PER_URL_PARAMS};
+pub use report::{write_report, ReportFormat, Skip, Transfer};
+use retry::Backoff;
+
+pub const VERSION: &str = "2025.11.09-rust";
+pub const PROGRAM_NAME: &str = "wcurl";
+
+/// Why a run failed, along with the exit code curl reported for it, if any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunError {
+    pub message: String,
+    pub curl_exit_code: Option<u32>,
+}
+
+impl From<String> for RunError {
+    fn from(message: String) -> Self {
+        RunError {
+            message,
+            curl_exit_code: None,
+        }
+    }
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Downloads everything described by `config` with the configured backend.
+///
+/// With [`Backend::Auto`], curl is used if it can be run and the native
+/// backend otherwise.
+pub fn execute(config: &Config) -> Result<(), RunError> {
+    match config.backend {
+        Backend::Curl => exec_curl(config),
+        Backend::Native => exec_native(config),
+        Backend::Auto => match CurlInfo::detect(&config.curl) {
+            Ok(curl) => run_curl(config, &curl),
+            Err(e) if config.curl == "curl" => {
+                eprintln!("Note: {}; using the native backend", e);
+                exec_native(config)
+            }
+            Err(e) => Err(e.into()),
+        },
+    }
+}
+
+/// Plans the downloads described by `config` and either runs curl or,
+/// with `--dry-run`, prints the command that would be run.
+pub fn exec_curl(config: &Config) -> Result<(), RunError> {
+    let curl = CurlInfo::detect(&config.curl)?;
+    run_curl(config, &curl)
+}
+
+fn run_curl(config: &Config, curl: &CurlInfo) -> Result<(), RunError> {
+    let mut plan = DownloadPlan::from_config(config)?;
+    plan.resolve_collisions()?;
+    plan.prepare_resume(config.dry_run)?;
+    plan.check_existing()?;
+
+    let manifest = match config.checksum_file {
+        Some(ref source) => load_checksum_file(config, source, |plan| {
+            let invocation = plan.curl_invocation(curl);
+            if config.dry_run {
+                println!("{}", invocation);
+                return Ok(None);
+            }
+            invocation.run_capture().map(Some)
+        })?,
+        None => None,
+    };
+
+    if plan.downloads.is_empty() {
+        if config.dry_run {
+            Ok(())
+        } else {
+            complete(config, &plan, Vec::new(), None)
+        }
+    } else if config.dry_run {
+        print_mapping(&plan);
+        for invocation in plan.curl_invocations(curl) {
+            println!("{}", invocation);
+        }
+        Ok(())
+    } else {
+        create_output_dir(config)?;
+        plan.reserve_staging()?;
+        let mut transfers = match plan.run_curl(curl) {
+            Ok(transfers) => transfers,
+            Err(e) => {
+                plan.release_staging(plan.downloads.len())?;
+                return Err(e.into());
+            }
+        };
+
+        if plan.resume {
+            let restart = plan.resume_restarts(&transfers)?;
+            if !restart.is_empty() {
+                let retry = DownloadPlan {
+                    downloads: restart
+                        .iter()
+                        .map(|&idx| Download {
+                            if_range: None,
+                            ..plan.downloads[idx].clone()
+                        })
+                        .collect(),
+                    ..plan.clone()
+                };
+                for (idx, transfer) in restart.into_iter().zip(retry.run_curl(curl)?) {
+                    transfers[idx] = transfer;
+                }
+            }
+        }
+        retry_failed(&mut plan, curl, &mut transfers)?;
+
+        complete(config, &plan, transfers, manifest.as_ref())
+    }
+}
+
+/// Runs curl again for downloads that failed in ways curl doesn't retry
+/// itself, such as a connection dropped mid-transfer, waiting between
+/// rounds with wcurl's jittered exponential backoff.
+fn retry_failed(
+    plan: &mut DownloadPlan,
+    curl: &CurlInfo,
+    transfers: &mut [Transfer],
+) -> Result<(), String> {
+    let mut backoff = Backoff::for_plan(plan);
+
+    loop {
+        let failed: Vec<usize> = transfers
+            .iter()
+            .enumerate()
+            .filter(|(_, transfer)| {
+                !transfer.succeeded()
+                    && transfer
+                        .exit_code
+                        .is_some_and(|code| retry::wcurl_retries(plan, curl, code))
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+        if failed.is_empty() {
+            return Ok(());
+        }
+
+        let retries_left = backoff.retries_left();
+        let wait = match backoff.next_delay(None) {
+            Some(wait) => wait,
+            None => return Ok(()),
+        };
+        eprintln!(
+            "Warning: {} download(s) failed. Will retry in {:.1} seconds. {} retries left.",
+            failed.len(),
+            wait.as_secs_f64(),
+            retries_left
+        );
+        thread::sleep(wait);
+
+        plan.reload_resume(&failed)?;
+        for (&idx, transfer) in failed.iter().zip(plan.run_curl_for(curl, &failed)?) {
+            transfers[idx] = transfer;
+        }
+    }
+}
+
+/// Downloads everything described by `config` with the built-in HTTP(S)
+/// client instead of curl.
+pub fn exec_native(config: &Config) -> Result<(), RunError> {
+    let mut plan = DownloadPlan::from_config(config)?;
+    plan.resolve_collisions()?;
+    plan.prepare_resume(config.dry_run)?;
+    plan.check_existing()?;
+    let options = NativeOptions::from_plan(&plan)?;
+
+    let manifest = match config.checksum_file {
+        Some(ref source) => load_checksum_file(config, source, |plan| {
+            if config.dry_run {
+                println!("# native: {} -> checksum file", plan.downloads[0].url);
+                return Ok(None);
+            }
+            native::fetch(&plan.downloads[0].url, &options).map(Some)
+        })?,
+        None => None,
+    };
+
+    if config.dry_run {
+        print_mapping(&plan);
+        println!("# downloading with the native backend");
+        Ok(())
+    } else {
+        create_output_dir(config)?;
+        plan.reserve_staging()?;
+        let transfers = match native::run_plan(&plan) {
+            Ok(transfers) => transfers,
+            Err(e) => {
+                plan.release_staging(plan.downloads.len())?;
+                return Err(e.into());
+            }
+        };
+        complete(config, &plan, transfers, manifest.as_ref())
+    }
+}
+
+/// Moves finished downloads into place and writes the `--report` file,
+/// which is written even when some downloads failed. The error carries the
+/// exit code of the first transfer curl reported as failed.
+fn complete(
+    config: &Config,
+    plan: &DownloadPlan,
+    mut transfers: Vec<Transfer>,
+    manifest: Option<&ChecksumManifest>,
+) -> Result<(), RunError> {
+    let result = plan.complete(&mut transfers, manifest);
+    transfers.extend(plan.skipped.iter().cloned());
+    let written = match config.report {
+        Some(ref path) => write_report(path, &transfers, config.report_format),
+        None => Ok(()),
+    };
+
+    let message = match (result, written) {
+        (Ok(()), Ok(())) => return Ok(()),
+        (Err(e), Err(report_error)) => format!("{}\n{}", e, report_error),
+        (Err(e), Ok(())) | (Ok(()), Err(e)) => e,
+    };
+    Err(RunError {
+        message,
+        curl_exit_code: transfers
+            .iter()
+            .filter_map(|transfer| transfer.exit_code)
+            .find(|&code| code != 0),
+    })
+}
+
+fn print_mapping(plan: &DownloadPlan) {
+    for download in &plan.downloads {
+        println!("# {} -> {}", download.url, download.output);
+    }
+}
+
+fn create_output_dir(config: &Config) -> Result<(), String> {
+    if let Some(ref dir) = config.output_dir {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir, e))?;
+    }
+    Ok(())
+}
+
+/// Reads a checksum manifest from a local path, or downloads it with the
+/// same defaults as everything else when `source` is a URL.
+///
+/// `fetch` is given a single-download plan whose output is standard output,
+/// and returns the body, or `None` in dry-run mode.
+fn load_checksum_file<F>(
+    config: &Config,
+    source: &str,
+    fetch: F,
+) -> Result<Option<ChecksumManifest>, String>
+where
+    F: FnOnce(&DownloadPlan) -> Result<Option<Vec<u8>>, String>,
+{
+    if !source.contains("://") {
+        return ChecksumManifest::from_file(source).map(Some);
+    }
+
+    let plan = DownloadPlan {
+        downloads: vec![Download {
+            url: encode_whitespace(source),
+            output: "-".to_string(),
+            checksum: None,
+            if_range: None,
+            remote_header_name: false,
+            explicit_output: true,
+        }],
+        resume: false,
+        ..DownloadPlan::from_config(config)?
+    };
+
+    let body = match fetch(&plan)? {
+        Some(body) => body,
+        None => return Ok(None),
+    };
+    let text = String::from_utf8(body).map_err(|_| format!("{} is not valid UTF-8", source))?;
+    ChecksumManifest::parse(&text, source).map(Some)
+}
@@ -1,295 +1,404 @@
-use std::env;
-use std::process::{exit, Command};
-
-const VERSION: &str = "2025.11.09-rust";
-const PROGRAM_NAME: &str = "wcurl";
-
-#[derive(Debug)]
-struct Config {
-    curl_options: Vec<String>,
-    urls: Vec<String>,
-    output_path: Option<String>,
-    decode_filename: bool,
-    dry_run: bool,
-}
-
-impl Config {
-    fn new() -> Self {
-        Config {
-            curl_options: Vec::new(),
-            urls: Vec::new(),
-            output_path: None,
-            decode_filename: true,
-            dry_run: false,
-        }
-    }
-}
-
-fn main() {
-    if let Err(e) = run() {
-        eprintln!("Error: {}", e);
-        exit(1);
-    }
-}
-
-fn run() -> Result<(), String> {
-    let args: Vec<String> = env::args().collect();
-
-    if args.len() < 2 {
-        print_usage();
-
-        return Err("No arguments provided".to_string());
-    }
-
-    let config = parse_args(args)?;
-    exec_curl(config)
-}
-
-fn parse_args(args: Vec<String>) -> Result<Config, String> {
-    let mut config = Config::new();
-
-    let mut iter = args.into_iter().skip(1).peekable();
-    let mut reading_urls = false;
-
-    while let Some(arg) = iter.next() {
-        if reading_urls {
-            config.urls.push(encode_whitespace(&arg));
-            continue;
-        }
-
-        match arg.as_str() {
-            "-h" | "--help" => {
-                print_usage();
-                exit(0);
-            }
-            "-V" | "--version" => {
-                println!("{}", VERSION);
-                exit(0);
-            }
-            "--dry-run" => config.dry_run = true,
-            "--no-decode-filename" => config.decode_filename = false,
-            "--" => reading_urls = true,
-
-            "--curl-options" => {
-                let opt = iter.next().ok_or("--curl-options requires an argument")?;
-                config.curl_options.push(opt);
-            }
-            "-o" | "-O" | "--output" => {
-                let opt = iter.next().ok_or(format!("{} requires an argument", arg))?;
-                config.output_path = Some(opt);
-            }
-
-            x if x.starts_with("--curl-options=") => {
-                let val = x.strip_prefix("--curl-options=").unwrap();
-                config.curl_options.push(val.to_string());
-            }
-            x if x.starts_with("--output=") => {
-                let val = x.strip_prefix("--output=").unwrap();
-                config.output_path = Some(val.to_string());
-            }
-            x if x.starts_with("-") => {
-                if x.starts_with("-o") || x.starts_with("-O") {
-                    if x.len() > 2 {
-                        config.output_path = Some(x[2..].to_string());
-                    } else {
-                        let opt = iter.next().ok_or(format!("{} requires an argument", x))?;
-                        config.output_path = Some(opt);
-                    }
-                } else {
-                    return Err(format!("Unknown option: '{}'", x));
-                }
-            }
-
-            url => {
-                config.urls.push(encode_whitespace(url));
-            }
-        }
-    }
-
-    if config.urls.is_empty() {
-        return Err("You must provide at least one URL to download.".to_string());
-    }
-
-    Ok(config)
-}
-
-fn encode_whitespace(url: &str) -> String {
-    url.replace(' ', "%20")
-}
-
-fn get_curl_version() -> Result<(u32, u32), String> {
-    let output = Command::new("curl")
-        .arg("--version")
-        .output()
-        .map_err(|e| format!("Failed to execute curl: {}", e))?;
-
-    let version_str = String::from_utf8_lossy(&output.stdout);
-    let first_line = version_str.lines().next().ok_or("No version output")?;
-
-    let parts: Vec<&str> = first_line.split_whitespace().collect();
-    if parts.len() < 2 {
-        return Err("Could not parse curl version".to_string());
-    }
-
-    let version = parts[1];
-    let (major_str, minor_str) = version.split_once('.').ok_or("Invalid version format")?;
-
-    let major = major_str
-        .parse::<u32>()
-        .map_err(|_| "Invalid major version")?;
-
-    let minor = minor_str
-        .split('.')
-        .next()
-        .unwrap_or("0")
-        .parse::<u32>()
-        .map_err(|_| "Invalid minor version")?;
-
-    Ok((major, minor))
-}
-
-fn get_url_filename(url: &str, decode: bool) -> String {
-    let url_path = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
-
-    let path_no_query = url_path.split(&['?', '#'][..]).next().unwrap_or(url_path);
-
-    let filename = path_no_query.rsplit('/').next().unwrap_or("");
-
-    if filename.is_empty() {
-        return "index.html".to_string();
-    }
-
-    if decode {
-        percent_decode(filename)
-    } else {
-        filename.to_string()
-    }
-}
-
-fn percent_decode(s: &str) -> String {
-    let mut result = String::with_capacity(s.len());
-    let mut chars = s.chars().peekable();
-
-    while let Some(c) = chars.next() {
-        if c == '%' {
-            let mut lookahead = chars.clone();
-            let h1 = lookahead.next();
-            let h2 = lookahead.next();
-
-            if let (Some(d1), Some(d2)) = (h1, h2) {
-                let hex_str = format!("{}{}", d1, d2);
-                if let Ok(byte) = u8::from_str_radix(&hex_str, 16) {
-                    if byte >= 0x20 && !is_unsafe_char(byte) {
-                        result.push(byte as char);
-
-                        chars.next();
-                        chars.next();
-                        continue;
-                    }
-                }
-            }
-        }
-        result.push(c);
-    }
-
-    result
-}
-
-fn is_unsafe_char(byte: u8) -> bool {
-    byte == 0x2F || byte == 0x5C
-}
-
-fn exec_curl(config: Config) -> Result<(), String> {
-    let (major, minor) = get_curl_version()?;
-
-    let mut command = Command::new("curl");
-
-    if config.urls.len() >= 2 {
-        if major >= 8 || (major == 7 && minor >= 66) {
-            command.arg("--parallel");
-            if major >= 8 && minor >= 16 {
-                command.args(["--parallel-max-host", "5"]);
-            }
-        }
-    }
-
-    let per_url_params = [
-        "--fail",
-        "--globoff",
-        "--location",
-        "--proto-default",
-        "https",
-        "--remote-time",
-        "--retry",
-        "5",
-    ];
-
-    let use_no_clobber = major >= 8 || (major == 7 && minor >= 83);
-
-    for (idx, url) in config.urls.iter().enumerate() {
-        if idx > 0 {
-            command.arg("--next");
-        }
-
-        command.args(&per_url_params);
-
-        if use_no_clobber {
-            command.arg("--no-clobber");
-        }
-
-        let output = if let Some(ref path) = config.output_path {
-            path.clone()
-        } else {
-            get_url_filename(url, config.decode_filename)
-        };
-
-        command.arg("--output").arg(output);
-
-        command.args(&config.curl_options);
-
-        command.arg(url);
-    }
-
-    if config.dry_run {
-        print!("curl");
-        for arg in command.get_args() {
-            print!(" {}", arg.to_string_lossy());
-        }
-        println!();
-        Ok(())
-    } else {
-        let status = command
-            .status()
-            .map_err(|e| format!("Failed to execute curl: {}", e))?;
-
-        if status.success() {
-            Ok(())
-        } else {
-            Err(format!("curl exited with status: {}", status))
-        }
-    }
-}
-
-fn print_usage() {
-    println!(
-        "{} -- a simple wrapper around curl to easily download files.\n",
-        PROGRAM_NAME
-    );
-    println!("Usage: {} <URL>...", PROGRAM_NAME);
-    println!("       {} [--curl-options <CURL_OPTIONS>]... [--no-decode-filename] [-o|-O|--output <PATH>] [--dry-run] [--] <URL>...", PROGRAM_NAME);
-    println!("       {} [--curl-options=<CURL_OPTIONS>]... [--no-decode-filename] [--output=<PATH>] [--dry-run] [--] <URL>...", PROGRAM_NAME);
-    println!("       {} -h|--help", PROGRAM_NAME);
-    println!("       {} -V|--version\n", PROGRAM_NAME);
-    println!("Options:\n");
-    println!(
-        "  --curl-options <CURL_OPTIONS>: Specify extra options to be passed when invoking curl."
-    );
-    println!("                                 May be specified more than once.\n");
-    println!("  -o, -O, --output <PATH>: Use the provided output path instead of getting it from the URL.");
-    println!("                           If multiple URLs are provided, resulting files share the same name");
-    println!("                           (curl behavior depends on version).\n");
-    println!("  --no-decode-filename: Don't percent-decode the output filename.\n");
-    println!("  --dry-run: Don't actually execute curl, just print what would be invoked.\n");
-    println!("  -V, --version: Print version information.\n");
-    println!("  -h, --help: Print this usage message.\n");
-}
+use std::env;
+use std::process::exit;
+
+use wcurl::{
+    encode_whitespace, execute, explain_exit_code, read_url_file, shell, Config, ExitCodeMode,
+    PROGRAM_NAME, VERSION,
+};
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {}", e);
+        exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 2 {
+        print_usage();
+
+        return Err("No arguments provided".to_string());
+    }
+
+    let mut config = Config::new();
+    let no_config = args
+        .iter()
+        .skip(1)
+        .take_while(|arg| *arg != "--")
+        .any(|arg| arg == "--no-config");
+    if !no_config {
+        config.load_default_files()?;
+    }
+    if let Some(curl) = env::var_os("WCURL_CURL").filter(|curl| !curl.is_empty()) {
+        config.curl = curl.to_string_lossy().into_owned();
+    }
+
+    let config = parse_args(args, config)?;
+    if let Err(e) = execute(&config) {
+        eprintln!("Error: {}", e);
+        if let Some(code) = e.curl_exit_code {
+            if let Some(explanation) = explain_exit_code(code) {
+                eprintln!("curl exit code {}: {}", code, explanation);
+            }
+            if config.exit_code_mode == ExitCodeMode::Curl {
+                exit(i32::try_from(code).unwrap_or(1));
+            }
+        }
+        exit(1);
+    }
+
+    Ok(())
+}
+
+fn parse_args(args: Vec<String>, mut config: Config) -> Result<Config, String> {
+    let mut iter = args.into_iter().skip(1).peekable();
+    let mut reading_urls = false;
+
+    while let Some(arg) = iter.next() {
+        if reading_urls {
+            config.urls.push(encode_whitespace(&arg));
+            continue;
+        }
+
+        match arg.as_str() {
+            "-h" | "--help" => {
+                print_usage();
+                exit(0);
+            }
+            "-V" | "--version" => {
+                println!("{}", VERSION);
+                exit(0);
+            }
+            "--dry-run" => config.dry_run = true,
+            "--no-decode-filename" => config.decode_filename = false,
+            "-J" | "--remote-header-name" => config.remote_header_name = true,
+            "--normalize-filename" => config.normalize_filename = true,
+            "--no-config" => {}
+            "-c" | "--continue" => config.resume = true,
+            "--ignore-case" => config.ignore_case = true,
+            "--sequential" => config.parallel = false,
+            "--retry-all-errors" => config.retry_all_errors = true,
+            "--retry-connrefused" => config.retry_connrefused = true,
+            "--" => reading_urls = true,
+
+            "--curl-options" => {
+                let opt = iter.next().ok_or("--curl-options requires an argument")?;
+                config.curl_options.extend(shell::split(&opt)?);
+            }
+            "--curl-option" => {
+                let opt = iter.next().ok_or("--curl-option requires an argument")?;
+                config.curl_options.push(opt);
+            }
+            "--checksum" => {
+                let opt = iter.next().ok_or("--checksum requires an argument")?;
+                config.checksums.push(opt.parse()?);
+            }
+            "--checksum-file" => {
+                let opt = iter.next().ok_or("--checksum-file requires an argument")?;
+                config.checksum_file = Some(opt);
+            }
+            "--curl" => {
+                let opt = iter.next().ok_or("--curl requires an argument")?;
+                config.curl = opt;
+            }
+            "--sanitize" => {
+                let opt = iter.next().ok_or("--sanitize requires an argument")?;
+                config.sanitize = opt.parse()?;
+            }
+            "--on-conflict" => {
+                let opt = iter.next().ok_or("--on-conflict requires an argument")?;
+                config.on_conflict = opt.parse()?;
+            }
+            "--on-collision" => {
+                let opt = iter.next().ok_or("--on-collision requires an argument")?;
+                config.on_collision = opt.parse()?;
+            }
+            "--report" => {
+                let opt = iter.next().ok_or("--report requires an argument")?;
+                config.report = Some(opt);
+            }
+            "--report-format" => {
+                let opt = iter.next().ok_or("--report-format requires an argument")?;
+                config.report_format = opt.parse()?;
+            }
+            "--exit-code-mode" => {
+                let opt = iter.next().ok_or("--exit-code-mode requires an argument")?;
+                config.exit_code_mode = opt.parse()?;
+            }
+            "--retries" => {
+                let opt = iter.next().ok_or("--retries requires an argument")?;
+                config.retries = parse_count("--retries", &opt)?;
+            }
+            "--retry-delay" => {
+                let opt = iter.next().ok_or("--retry-delay requires an argument")?;
+                config.retry_delay = Some(parse_count("--retry-delay", &opt)?);
+            }
+            "--retry-max-time" => {
+                let opt = iter.next().ok_or("--retry-max-time requires an argument")?;
+                config.retry_max_time = Some(parse_count("--retry-max-time", &opt)?);
+            }
+            "--parallel-max" => {
+                let opt = iter.next().ok_or("--parallel-max requires an argument")?;
+                config.parallel_max = Some(parse_count("--parallel-max", &opt)?);
+            }
+            "--parallel-max-host" => {
+                let opt = iter
+                    .next()
+                    .ok_or("--parallel-max-host requires an argument")?;
+                config.parallel_max_host = Some(parse_count("--parallel-max-host", &opt)?);
+            }
+            "--backend" => {
+                let opt = iter.next().ok_or("--backend requires an argument")?;
+                config.backend = opt.parse()?;
+            }
+            "-i" | "--input-file" => {
+                let opt = iter.next().ok_or(format!("{} requires an argument", arg))?;
+                config.urls.extend(read_url_file(&opt)?);
+            }
+            "-o" | "-O" | "--output" => {
+                let opt = iter.next().ok_or(format!("{} requires an argument", arg))?;
+                config.output_paths.push(opt);
+            }
+
+            x if x.starts_with("--curl-options=") => {
+                let val = x.strip_prefix("--curl-options=").unwrap();
+                config.curl_options.extend(shell::split(val)?);
+            }
+            x if x.starts_with("--curl-option=") => {
+                let val = x.strip_prefix("--curl-option=").unwrap();
+                config.curl_options.push(val.to_string());
+            }
+            x if x.starts_with("--checksum=") => {
+                let val = x.strip_prefix("--checksum=").unwrap();
+                config.checksums.push(val.parse()?);
+            }
+            x if x.starts_with("--checksum-file=") => {
+                let val = x.strip_prefix("--checksum-file=").unwrap();
+                config.checksum_file = Some(val.to_string());
+            }
+            x if x.starts_with("--curl=") => {
+                let val = x.strip_prefix("--curl=").unwrap();
+                config.curl = val.to_string();
+            }
+            x if x.starts_with("--sanitize=") => {
+                let val = x.strip_prefix("--sanitize=").unwrap();
+                config.sanitize = val.parse()?;
+            }
+            x if x.starts_with("--on-conflict=") => {
+                let val = x.strip_prefix("--on-conflict=").unwrap();
+                config.on_conflict = val.parse()?;
+            }
+            x if x.starts_with("--on-collision=") => {
+                let val = x.strip_prefix("--on-collision=").unwrap();
+                config.on_collision = val.parse()?;
+            }
+            x if x.starts_with("--report=") => {
+                let val = x.strip_prefix("--report=").unwrap();
+                config.report = Some(val.to_string());
+            }
+            x if x.starts_with("--report-format=") => {
+                let val = x.strip_prefix("--report-format=").unwrap();
+                config.report_format = val.parse()?;
+            }
+            x if x.starts_with("--exit-code-mode=") => {
+                let val = x.strip_prefix("--exit-code-mode=").unwrap();
+                config.exit_code_mode = val.parse()?;
+            }
+            x if x.starts_with("--retries=") => {
+                let val = x.strip_prefix("--retries=").unwrap();
+                config.retries = parse_count("--retries", val)?;
+            }
+            x if x.starts_with("--retry-delay=") => {
+                let val = x.strip_prefix("--retry-delay=").unwrap();
+                config.retry_delay = Some(parse_count("--retry-delay", val)?);
+            }
+            x if x.starts_with("--retry-max-time=") => {
+                let val = x.strip_prefix("--retry-max-time=").unwrap();
+                config.retry_max_time = Some(parse_count("--retry-max-time", val)?);
+            }
+            x if x.starts_with("--parallel-max=") => {
+                let val = x.strip_prefix("--parallel-max=").unwrap();
+                config.parallel_max = Some(parse_count("--parallel-max", val)?);
+            }
+            x if x.starts_with("--parallel-max-host=") => {
+                let val = x.strip_prefix("--parallel-max-host=").unwrap();
+                config.parallel_max_host = Some(parse_count("--parallel-max-host", val)?);
+            }
+            x if x.starts_with("--backend=") => {
+                let val = x.strip_prefix("--backend=").unwrap();
+                config.backend = val.parse()?;
+            }
+            x if x.starts_with("--input-file=") => {
+                let val = x.strip_prefix("--input-file=").unwrap();
+                config.urls.extend(read_url_file(val)?);
+            }
+            "--output-dir" => {
+                let opt = iter.next().ok_or("--output-dir requires an argument")?;
+                config.output_dir = Some(opt);
+            }
+            x if x.starts_with("--output-dir=") => {
+                let val = x.strip_prefix("--output-dir=").unwrap();
+                config.output_dir = Some(val.to_string());
+            }
+            x if x.starts_with("--output=") => {
+                let val = x.strip_prefix("--output=").unwrap();
+                config.output_paths.push(val.to_string());
+            }
+            x if x.starts_with("-") => {
+                if x.starts_with("-o") || x.starts_with("-O") {
+                    if x.len() > 2 {
+                        config.output_paths.push(x[2..].to_string());
+                    } else {
+                        let opt = iter.next().ok_or(format!("{} requires an argument", x))?;
+                        config.output_paths.push(opt);
+                    }
+                } else if let Some(val) = x.strip_prefix("-i") {
+                    config.urls.extend(read_url_file(val)?);
+                } else {
+                    return Err(format!("Unknown option: '{}'", x));
+                }
+            }
+
+            url => {
+                config.urls.push(encode_whitespace(url));
+            }
+        }
+    }
+
+    if config.urls.is_empty() {
+        return Err("You must provide at least one URL to download.".to_string());
+    }
+
+    Ok(config)
+}
+
+/// Parses the value of a numeric option such as `--retries`.
+fn parse_count(option: &str, value: &str) -> Result<u32, String> {
+    value.parse().map_err(|_| {
+        format!(
+            "Invalid {} value '{}': expected a non-negative integer",
+            option, value
+        )
+    })
+}
+
+fn print_usage() {
+    println!(
+        "{} -- a simple wrapper around curl to easily download files.\n",
+        PROGRAM_NAME
+    );
+    println!("Usage: {} <URL>...", PROGRAM_NAME);
+    println!("       {} [--curl-options <CURL_OPTIONS>]... [--curl-option <ARG>]... [-i|--input-file <PATH>]... [--checksum <ALGO:HEX>]... [--checksum-file <PATH|URL>] [--no-decode-filename] [--normalize-filename] [-J|--remote-header-name] [--sanitize <posix|windows|portable>] [-o|-O|--output <PATH>]... [--output-dir <DIR>] [-c|--continue] [--on-conflict <fail|overwrite|rename|skip>] [--on-collision <abort|prefix-host>] [--ignore-case] [--report <PATH|->] [--report-format <json|ndjson>] [--exit-code-mode <curl|simple>] [--retries <N>] [--retry-delay <SECONDS>] [--retry-max-time <SECONDS>] [--retry-all-errors] [--retry-connrefused] [--parallel-max <N>] [--parallel-max-host <N>] [--sequential] [--curl <PATH>] [--backend <auto|curl|native>] [--no-config] [--dry-run] [--] <URL>...", PROGRAM_NAME);
+    println!("       {} [--curl-options=<CURL_OPTIONS>]... [--curl-option=<ARG>]... [--input-file=<PATH>]... [--checksum=<ALGO:HEX>]... [--checksum-file=<PATH|URL>] [--no-decode-filename] [--normalize-filename] [-J|--remote-header-name] [--sanitize=<posix|windows|portable>] [--output=<PATH>]... [--output-dir=<DIR>] [-c|--continue] [--on-conflict=<fail|overwrite|rename|skip>] [--on-collision=<abort|prefix-host>] [--ignore-case] [--report=<PATH|->] [--report-format=<json|ndjson>] [--exit-code-mode=<curl|simple>] [--retries=<N>] [--retry-delay=<SECONDS>] [--retry-max-time=<SECONDS>] [--retry-all-errors] [--retry-connrefused] [--parallel-max=<N>] [--parallel-max-host=<N>] [--sequential] [--curl=<PATH>] [--backend=<auto|curl|native>] [--no-config] [--dry-run] [--] <URL>...", PROGRAM_NAME);
+    println!("       {} -h|--help", PROGRAM_NAME);
+    println!("       {} -V|--version\n", PROGRAM_NAME);
+    println!("Options:\n");
+    println!(
+        "  --curl-options <CURL_OPTIONS>: Specify extra options to be passed when invoking curl."
+    );
+    println!("                                 Split into words like a POSIX shell would.");
+    println!("                                 May be specified more than once.\n");
+    println!("  --curl-option <ARG>: Pass ARG to curl as a single argument, without splitting.");
+    println!("                       May be specified more than once.\n");
+    println!("  -i, --input-file <PATH>: Read URLs from PATH, one per line, or from stdin if PATH is '-'.");
+    println!("                           Blank lines and lines starting with '#' are ignored.");
+    println!("                           May be specified more than once.\n");
+    println!("  --checksum <ALGO:HEX>: Verify the download against the expected digest.");
+    println!("                         ALGO is one of sha256, sha512, sha1 or md5.");
+    println!("                         Matched positionally when multiple URLs are given.");
+    println!("                         A mismatching file is renamed to <file>.corrupt.\n");
+    println!("  --checksum-file <PATH|URL>: Verify downloads against a checksum listing such as SHA256SUMS,");
+    println!("                              in GNU coreutils or BSD format. Files are looked up by output name");
+    println!(
+        "                              and any missing from the listing are reported as errors.\n"
+    );
+    println!("  -o, -O, --output <PATH>: Use the provided output path instead of getting it from the URL.");
+    println!(
+        "                           May be specified once per URL; the Nth output path is used for"
+    );
+    println!("                           the Nth URL, and URLs without one get their name from the URL.\n");
+    println!("  --output-dir <DIR>: Save files inside DIR, creating it if needed ('-o -' still writes to stdout).\n");
+    println!(
+        "  -c, --continue: Download into <file>.part and resume it on the next run, restarting"
+    );
+    println!("                  from scratch if the remote file changed. The file is moved to its");
+    println!("                  final name once complete; existing final files are skipped.\n");
+    println!("  --on-conflict <fail|overwrite|rename|skip>: What to do when an output file already exists.");
+    println!("                                              'rename' (the default) saves the download as");
+    println!("                                              'name (1).ext'; 'skip' keeps the existing file;");
+    println!("                                              'fail' reports an error without downloading.\n");
+    println!("  --on-collision <abort|prefix-host>: What to do when several URLs would be saved to the same");
+    println!("                                      file. 'prefix-host' (the default) prefixes names taken");
+    println!("                                      from URLs with their host, and their directories on a");
+    println!("                                      shared host, numbering names that still clash; 'abort'");
+    println!("                                      lists the collisions and stops. Repeated URLs are only");
+    println!("                                      downloaded once.\n");
+    println!("  --ignore-case: Treat output names that differ only in case as the same file.\n");
+    println!("  --report <PATH|->: Write a JSON report of every transfer to PATH, or to stdout if PATH is '-':");
+    println!("                     its status, output path, HTTP status, effective URL, bytes, duration,");
+    println!("                     content type, curl exit code and error message.\n");
+    println!(
+        "  --report-format <json|ndjson>: Write the report as one JSON array (the default) or as"
+    );
+    println!("                                 one JSON object per line.\n");
+    println!(
+        "  --exit-code-mode <curl|simple>: Exit with curl's own exit code when a download fails"
+    );
+    println!("                                  (the default), or always with 1 ('simple').\n");
+    println!(
+        "  --retries <N>: Retry a failed download up to N times (default 5, 0 to never retry)."
+    );
+    println!("                 curl retries timeouts and HTTP 408, 429 and 5xx responses; wcurl");
+    println!("                 retries dropped connections and empty replies itself, waiting");
+    println!("                 1, 2, 4... seconds with random jitter between rounds.\n");
+    println!("  --retry-delay <SECONDS>: Wait this long between retries instead of backing off.\n");
+    println!("  --retry-max-time <SECONDS>: Don't start new retries after this many seconds (0 for no limit).\n");
+    println!("  --retry-all-errors: Retry every failure, including HTTP 404 and other errors.\n");
+    println!("  --retry-connrefused: Also retry connections the server refused.\n");
+    println!("  --parallel-max <N>: Download at most N files at once (1 to 300, curl's default is 50).\n");
+    println!("  --parallel-max-host <N>: Open at most N connections to any one host.");
+    println!("                           curl 8.16.0 and later default to 5; with older");
+    println!("                           curl, wcurl runs a curl per URL to respect it.\n");
+    println!("  --sequential: Download one file after another instead of in parallel.");
+    println!(
+        "                With curl older than 7.66.0, parallel downloads run a curl per URL.\n"
+    );
+    println!("  --no-decode-filename: Don't percent-decode the output filename.");
+    println!(
+        "                        Filenames are otherwise decoded as UTF-8; escapes that aren't"
+    );
+    println!(
+        "                        valid UTF-8, control characters and '/' or '\\' stay encoded.\n"
+    );
+    println!("  --normalize-filename: Convert filenames taken from URLs or Content-Disposition to");
+    println!("                        Unicode NFC, so names composed differently compare equal.\n");
+    println!(
+        "  -J, --remote-header-name: Name files after the server's Content-Disposition header,"
+    );
+    println!("                            falling back to the URL when it is absent or unsafe.");
+    println!("                            Doesn't apply to URLs given an --output path.\n");
+    println!("  --sanitize <posix|windows|portable>: Make filenames taken from URLs or Content-Disposition");
+    println!("                                       valid on these filesystems, replacing characters they");
+    println!("                                       reject with '_' and shortening names over 255 bytes.");
+    println!("                                       'portable' keeps only letters, digits, '.', '_' and '-'.");
+    println!("                                       Defaults to the current platform's rules.\n");
+    println!("  --curl <PATH>: Run the curl binary at PATH instead of searching $PATH.");
+    println!("                 Defaults to the WCURL_CURL environment variable if set.\n");
+    println!(
+        "  --backend <auto|curl|native>: Choose what performs the downloads. 'auto' (the default)"
+    );
+    println!(
+        "                                uses curl if it is installed and the built-in HTTP(S)"
+    );
+    println!("                                client otherwise. The built-in client only supports");
+    println!(
+        "                                the -H, -A, -e, -u, --max-redirs and -s curl options.\n"
+    );
+    println!("  --no-config: Don't read defaults from /etc/wcurl/config.toml or");
+    println!("               $XDG_CONFIG_HOME/wcurl/config.toml.\n");
+    println!("  --dry-run: Don't actually execute curl, just print what would be invoked.\n");
+    println!("  -V, --version: Print version information.\n");
+    println!("  -h, --help: Print this usage message.\n");
+}
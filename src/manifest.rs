@@ -0,0 +1,118 @@
+use std::fs;
+use std::path::Path;
+
+use crate::checksum::Checksum;
+use crate::digest::Algorithm;
+
+/// A parsed checksum listing such as `SHA256SUMS`.
+///
+/// Both the GNU coreutils format (`<hex>  <name>`, with an optional `*`
+/// marking binary mode) and the BSD tagged format (`SHA256 (<name>) = <hex>`)
+/// are understood; in the GNU format the algorithm is inferred from the
+/// digest length.
+#[derive(Debug, Clone, Default)]
+pub struct ChecksumManifest {
+    entries: Vec<(String, Checksum)>,
+}
+
+impl ChecksumManifest {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&text, &path.display().to_string())
+    }
+
+    /// Parses a manifest; `source` is only used to label errors.
+    pub fn parse(text: &str, source: &str) -> Result<Self, String> {
+        let mut entries = Vec::new();
+
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let entry = parse_bsd_line(line)
+                .or_else(|| parse_gnu_line(line))
+                .ok_or(format!("{}:{}: malformed checksum line", source, idx + 1))?;
+            let (name, algorithm, hex) = entry;
+            let checksum = Checksum::new(algorithm, hex)
+                .map_err(|e| format!("{}:{}: {}", source, idx + 1, e))?;
+
+            entries.push((name, checksum));
+        }
+
+        Ok(ChecksumManifest { entries })
+    }
+
+    /// Looks up the checksum for `output`, first by its exact path and then
+    /// by its file name alone.
+    pub fn lookup(&self, output: &str) -> Option<&Checksum> {
+        let normalize = |name: &str| name.trim_start_matches("./").to_string();
+        let file_name = |name: &str| {
+            Path::new(name)
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+        };
+
+        let wanted = normalize(output);
+        self.entries
+            .iter()
+            .find(|(name, _)| normalize(name) == wanted)
+            .or_else(|| {
+                let wanted = file_name(output)?;
+                self.entries
+                    .iter()
+                    .find(|(name, _)| file_name(name).as_deref() == Some(wanted.as_str()))
+            })
+            .map(|(_, checksum)| checksum)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+fn parse_bsd_line(line: &str) -> Option<(String, Algorithm, &str)> {
+    let (tag, rest) = line.split_once(" (")?;
+    let (name, hex) = rest.rsplit_once(") = ")?;
+    let algorithm = tag.replace('-', "").parse().ok()?;
+
+    Some((name.to_string(), algorithm, hex.trim()))
+}
+
+fn parse_gnu_line(line: &str) -> Option<(String, Algorithm, &str)> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+
+    let (hex, rest) = line.split_once(' ')?;
+    let name = rest.strip_prefix(' ').or_else(|| rest.strip_prefix('*'))?;
+    if name.is_empty() {
+        return None;
+    }
+
+    let algorithm = match hex.len() {
+        32 => Algorithm::Md5,
+        40 => Algorithm::Sha1,
+        64 => Algorithm::Sha256,
+        128 => Algorithm::Sha512,
+        _ => return None,
+    };
+
+    let name = if escaped {
+        name.replace("\\\\", "\u{0}")
+            .replace("\\n", "\n")
+            .replace('\u{0}', "\\")
+    } else {
+        name.to_string()
+    };
+
+    Some((name, algorithm, hex))
+}
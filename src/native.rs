@@ -0,0 +1,444 @@
+//! The built-in download backend, used when curl is unavailable or with
+//! `--backend native`. It applies the same defaults wcurl passes to curl:
+//! follow redirects, fail on HTTP errors, retry transient failures, never
+//! overwrite existing files, set the remote modification time and default
+//! to https.
+
+use std::fs::{File, FileTimes, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::http::{self, Error, Response, Url};
+use crate::plan::{Download, DownloadPlan};
+use crate::report::Transfer;
+use crate::resume::{self, PartialDownload};
+use crate::retry::Backoff;
+
+/// curl's default for `--max-redirs`.
+const DEFAULT_MAX_REDIRECTS: u32 = 50;
+
+/// Request settings for the native backend, translated from the subset of
+/// curl options it understands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NativeOptions {
+    pub headers: Vec<(String, String)>,
+    pub max_redirects: u32,
+    pub retries: u32,
+    /// Seconds between retries instead of the exponential backoff.
+    pub retry_delay: Option<u32>,
+    /// Seconds after which no more retries are started; 0 means no limit.
+    pub retry_max_time: Option<u32>,
+    pub retry_all_errors: bool,
+    pub retry_connrefused: bool,
+    /// Download into `.part` files and resume them (`--continue`).
+    pub resume: bool,
+}
+
+impl NativeOptions {
+    /// Translates curl options, failing on any the native backend can't honour.
+    pub fn from_curl_options(curl_options: &[String], retries: u32) -> Result<Self, String> {
+        let mut options = NativeOptions {
+            headers: Vec::new(),
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            retries,
+            retry_delay: None,
+            retry_max_time: None,
+            retry_all_errors: false,
+            retry_connrefused: false,
+            resume: false,
+        };
+
+        let mut iter = curl_options.iter();
+        while let Some(opt) = iter.next() {
+            let mut value = || {
+                iter.next()
+                    .ok_or(format!("curl option '{}' requires an argument", opt))
+            };
+
+            match opt.as_str() {
+                "-H" | "--header" => {
+                    let header = value()?;
+                    let (name, val) = header
+                        .split_once(':')
+                        .ok_or(format!("Invalid header '{}'", header))?;
+                    options
+                        .headers
+                        .push((name.trim().to_string(), val.trim().to_string()));
+                }
+                "-A" | "--user-agent" => {
+                    options
+                        .headers
+                        .push(("User-Agent".to_string(), value()?.clone()));
+                }
+                "-e" | "--referer" => {
+                    options
+                        .headers
+                        .push(("Referer".to_string(), value()?.clone()));
+                }
+                "-u" | "--user" => {
+                    let credentials = value()?;
+                    options.headers.push((
+                        "Authorization".to_string(),
+                        format!("Basic {}", http::base64(credentials.as_bytes())),
+                    ));
+                }
+                "--max-redirs" => {
+                    let max = value()?;
+                    options.max_redirects = max
+                        .parse()
+                        .map_err(|_| format!("Invalid --max-redirs value '{}'", max))?;
+                }
+                "-s"
+                | "--silent"
+                | "-S"
+                | "--show-error"
+                | "--no-progress-meter"
+                | "-#"
+                | "--progress-bar" => {}
+                _ => {
+                    return Err(format!(
+                        "The native backend does not support the curl option '{}'",
+                        opt
+                    ))
+                }
+            }
+        }
+
+        Ok(options)
+    }
+
+    pub fn from_plan(plan: &DownloadPlan) -> Result<Self, String> {
+        let mut options = NativeOptions::from_curl_options(&plan.curl_options, plan.retries)?;
+        options.retry_delay = plan.retry_delay;
+        options.retry_max_time = plan.retry_max_time;
+        options.retry_all_errors = plan.retry_all_errors;
+        options.retry_connrefused = plan.retry_connrefused;
+        options.resume = plan.resume;
+        Ok(options)
+    }
+}
+
+/// Downloads everything in `plan` one after another into their staging
+/// files, carrying on past failures, and returns how each one went.
+pub fn run_plan(plan: &DownloadPlan) -> Result<Vec<Transfer>, String> {
+    let options = NativeOptions::from_plan(plan)?;
+
+    let transfers = plan
+        .downloads
+        .iter()
+        .enumerate()
+        .map(|(idx, download)| {
+            let staging = plan.staging_path(idx);
+            let headers = plan.headers_path(idx);
+            let mut transfer = Transfer::new(&download.url);
+            let started = Instant::now();
+            let result = download_file(
+                download,
+                staging.as_deref(),
+                headers.as_deref(),
+                &options,
+                &mut transfer,
+            );
+            transfer.duration = Some(started.elapsed().as_secs_f64());
+            transfer.error = result.err();
+            transfer
+        })
+        .collect();
+
+    Ok(transfers)
+}
+
+/// Fetches `url` into memory.
+pub fn fetch(url: &str, options: &NativeOptions) -> Result<Vec<u8>, String> {
+    with_retries(options, || {
+        let mut response = get(url, options, &options.headers)?;
+        let mut body = Vec::new();
+        response.read_to_end(&mut body).map_err(|e| {
+            Error::io(
+                format!("Failed reading {}: {}", url, http::describe(&e)),
+                &e,
+            )
+        })?;
+        Ok(body)
+    })
+}
+
+/// Downloads one URL to `staging`, which the plan later moves to the
+/// download's output path, or to standard output without one. The response
+/// headers are saved to `headers` if given. Details of the response are
+/// recorded in `transfer`.
+pub fn download_file(
+    download: &Download,
+    staging: Option<&str>,
+    headers: Option<&str>,
+    options: &NativeOptions,
+    transfer: &mut Transfer,
+) -> Result<(), String> {
+    let staging = match staging {
+        Some(staging) => staging,
+        None => return download_to_stdout(download, options, transfer),
+    };
+    if options.resume {
+        return download_resumable(download, options, transfer);
+    }
+
+    let path = Path::new(staging);
+
+    with_retries(options, || {
+        let mut response =
+            get(&download.url, options, &options.headers).map_err(|e| record_error(transfer, e))?;
+        record_response(transfer, &response);
+        if let Some(headers) = headers {
+            resume::save_headers(headers, response.status, &response.headers)
+                .map_err(|e| write_error(Path::new(headers), e))?;
+        }
+        let mut file = File::create(path).map_err(|e| write_error(path, e))?;
+
+        transfer.bytes = Some(io::copy(&mut response, &mut file).map_err(|e| {
+            Error::io(
+                format!("Failed reading {}: {}", download.url, http::describe(&e)),
+                &e,
+            )
+        })?);
+
+        set_remote_time(&file, &response);
+        Ok(())
+    })
+}
+
+/// Writes the body of one URL to standard output, for `--output -`.
+fn download_to_stdout(
+    download: &Download,
+    options: &NativeOptions,
+    transfer: &mut Transfer,
+) -> Result<(), String> {
+    with_retries(options, || {
+        let mut response =
+            get(&download.url, options, &options.headers).map_err(|e| record_error(transfer, e))?;
+        record_response(transfer, &response);
+
+        let mut stdout = io::stdout().lock();
+        transfer.bytes = Some(io::copy(&mut response, &mut stdout).map_err(|e| {
+            Error::io(
+                format!("Failed reading {}: {}", download.url, http::describe(&e)),
+                &e,
+            )
+        })?);
+        stdout
+            .flush()
+            .map_err(|e| Error::fatal(format!("Failed to write to stdout: {}", e)))?;
+        Ok(())
+    })
+}
+
+/// Downloads into `<output>.part`, continuing from its current size when
+/// the saved validator says the remote file hasn't changed.
+fn download_resumable(
+    download: &Download,
+    options: &NativeOptions,
+    transfer: &mut Transfer,
+) -> Result<(), String> {
+    let output = download.output.as_str();
+    let part = PathBuf::from(resume::part_path(output));
+
+    with_retries(options, || {
+        let mut restarted = false;
+
+        let mut response = loop {
+            let partial = PartialDownload::find(output);
+            if partial.as_ref().is_some_and(PartialDownload::is_complete) {
+                return Ok(());
+            }
+
+            let mut headers = options.headers.clone();
+            let offset = match partial {
+                Some(PartialDownload {
+                    size,
+                    validator: Some(validator),
+                    ..
+                }) if size > 0 => {
+                    headers.push(("Range".to_string(), format!("bytes={}-", size)));
+                    headers.push(("If-Range".to_string(), validator));
+                    size
+                }
+                _ => 0,
+            };
+
+            let response = match get(&download.url, options, &headers) {
+                Err(e) if e.status == Some(416) && offset > 0 && !restarted => {
+                    resume::discard(output).map_err(Error::fatal)?;
+                    restarted = true;
+                    continue;
+                }
+                other => other.map_err(|e| record_error(transfer, e))?,
+            };
+            record_response(transfer, &response);
+
+            let resumed = response.status == 206;
+            if resumed && resume::range_start(&response.headers) != Some(offset) {
+                if restarted {
+                    return Err(Error::fatal("Server returned an unexpected range"));
+                }
+                resume::discard(output).map_err(Error::fatal)?;
+                restarted = true;
+                continue;
+            }
+
+            let headers = resume::headers_path(output);
+            resume::save_headers(&headers, response.status, &response.headers)
+                .map_err(|e| write_error(Path::new(&headers), e))?;
+            break (response, resumed);
+        };
+
+        let (ref mut body, resumed) = response;
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(resumed)
+            .truncate(!resumed)
+            .open(&part)
+            .map_err(|e| write_error(&part, e))?;
+
+        transfer.bytes = Some(io::copy(body, &mut file).map_err(|e| {
+            Error::io(
+                format!("Failed reading {}: {}", download.url, http::describe(&e)),
+                &e,
+            )
+        })?);
+
+        set_remote_time(&file, body);
+        Ok(())
+    })
+}
+
+fn record_response(transfer: &mut Transfer, response: &Response) {
+    transfer.http_status = Some(response.status);
+    transfer.effective_url = Some(response.url.clone());
+    transfer.content_type = response.header("Content-Type").map(str::to_string);
+}
+
+fn record_error(transfer: &mut Transfer, e: Error) -> Error {
+    if e.status.is_some() {
+        transfer.http_status = e.status;
+    }
+    e
+}
+
+fn write_error(path: &Path, e: io::Error) -> Error {
+    Error::fatal(format!("Failed to create {}: {}", path.display(), e))
+}
+
+fn set_remote_time(file: &File, response: &Response) {
+    let modified = response
+        .header("Last-Modified")
+        .and_then(http::parse_http_date);
+
+    if let Some(modified) = modified {
+        let _ = file.set_times(FileTimes::new().set_modified(modified));
+    }
+}
+
+/// Headers that carry credentials. Like curl without `--location-trusted`,
+/// they aren't sent on after a redirect to another scheme, host or port.
+const CREDENTIAL_HEADERS: [&str; 2] = ["Authorization", "Cookie"];
+
+/// Sends a GET for `url`, following redirects and turning HTTP error
+/// statuses into errors.
+fn get(
+    url: &str,
+    options: &NativeOptions,
+    headers: &[(String, String)],
+) -> Result<Response, Error> {
+    let mut current = Url::parse(url).map_err(Error::fatal)?;
+    let mut headers = headers.to_vec();
+    let mut redirects = 0;
+
+    loop {
+        let response = http::get(&current, &headers)?;
+
+        if matches!(response.status, 301 | 302 | 303 | 307 | 308) {
+            if let Some(location) = response.header("Location") {
+                if redirects >= options.max_redirects {
+                    return Err(Error::fatal(format!(
+                        "Maximum ({}) redirects followed",
+                        options.max_redirects
+                    )));
+                }
+                redirects += 1;
+                let next = current.join(location).map_err(Error::fatal)?;
+                if !same_origin(&current, &next) {
+                    headers.retain(|(name, _)| {
+                        !CREDENTIAL_HEADERS
+                            .iter()
+                            .any(|credential| name.eq_ignore_ascii_case(credential))
+                    });
+                }
+                current = next;
+                continue;
+            }
+        }
+
+        if response.status >= 400 {
+            let mut error = Error::fatal(format!(
+                "The requested URL returned error: {}",
+                response.status
+            ));
+            // The same statuses curl's --retry treats as transient.
+            error.transient = matches!(response.status, 408 | 429 | 500 | 502 | 503 | 504);
+            error.status = Some(response.status);
+            error.retry_after = response
+                .header("Retry-After")
+                .and_then(|secs| secs.parse().ok())
+                .map(Duration::from_secs);
+            return Err(error);
+        }
+
+        return Ok(response);
+    }
+}
+
+fn same_origin(a: &Url, b: &Url) -> bool {
+    a.scheme.eq_ignore_ascii_case(&b.scheme)
+        && a.host.eq_ignore_ascii_case(&b.host)
+        && a.port == b.port
+}
+
+/// Runs `attempt` until it succeeds, fails permanently or runs out of
+/// retries, waiting between tries as [`Backoff`] decides or as long as the
+/// server asked with `Retry-After`.
+fn with_retries<T>(
+    options: &NativeOptions,
+    mut attempt: impl FnMut() -> Result<T, Error>,
+) -> Result<T, String> {
+    let mut backoff = Backoff::new(options.retries, options.retry_delay, options.retry_max_time);
+
+    loop {
+        let e = match attempt() {
+            Ok(value) => return Ok(value),
+            Err(e) => e,
+        };
+        let retryable = e.transient
+            || options.retry_all_errors
+            || (options.retry_connrefused && e.connection_refused);
+        let retries_left = backoff.retries_left();
+        let wait = if retryable {
+            backoff.next_delay(e.retry_after)
+        } else {
+            None
+        };
+        match wait {
+            Some(wait) => {
+                eprintln!(
+                    "Warning: Problem: {}. Will retry in {:.1} seconds. {} retries left.",
+                    e,
+                    wait.as_secs_f64(),
+                    retries_left
+                );
+                thread::sleep(wait);
+            }
+            None => return Err(e.message),
+        }
+    }
+}
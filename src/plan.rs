@@ -0,0 +1,1497 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::iter;
+use std::mem;
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use crate::checksum::Checksum;
+use crate::config::{Config, OnCollision, OnConflict};
+use crate::curl::{Capability, CurlInfo};
+use crate::filename::{
+    content_disposition_filename, get_url_filename, normalize_filename, sanitize_filename,
+    sanitize_remote_filename, truncate_filename, SanitizeProfile, MAX_FILENAME_BYTES,
+};
+use crate::json;
+use crate::manifest::ChecksumManifest;
+use crate::report::{Skip, Transfer};
+use crate::resume::{self, PartialDownload};
+use crate::shell;
+use crate::staging;
+
+/// Options wcurl passes to curl for every URL, followed by `--retry` and
+/// the other retry settings.
+pub const PER_URL_PARAMS: [&str; 6] = [
+    "--fail",
+    "--globoff",
+    "--location",
+    "--proto-default",
+    "https",
+    "--remote-time",
+];
+
+/// The most transfers curl will run at once with `--parallel-max`.
+const MAX_PARALLEL: u32 = 300;
+
+/// curl's default for `--parallel-max`, which wcurl also uses when it runs
+/// downloads in parallel itself.
+const DEFAULT_PARALLEL_MAX: u32 = 50;
+
+/// The `--parallel-max-host` wcurl passes to curls that support it when
+/// none was given. Older curls aren't limited per host unless asked to.
+const DEFAULT_PARALLEL_MAX_HOST: u32 = 5;
+
+/// How many bytes of arguments wcurl puts on one curl command line: half of
+/// the smallest `ARG_MAX` of Linux and macOS (256 KiB on older macOS),
+/// leaving room for the environment, which counts against it too, and under
+/// the 32767 characters Windows allows.
+const MAX_COMMAND_LINE: usize = if cfg!(windows) { 30_000 } else { 128 * 1024 };
+
+/// What [`DownloadPlan::run_children`]'s workers share: the downloads still
+/// to start, how many curls are running per host, and the finished ones.
+struct Children {
+    pending: VecDeque<usize>,
+    active: HashMap<String, usize>,
+    done: HashMap<usize, Result<Transfer, String>>,
+}
+
+/// A single URL and the path it will be saved to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Download {
+    pub url: String,
+    pub output: String,
+    pub checksum: Option<Checksum>,
+    /// `If-Range` validator for resuming a `.part` file with `--continue`.
+    pub if_range: Option<String>,
+    /// Rename the file after the server's `Content-Disposition` filename,
+    /// if it sends a usable one (`--remote-header-name`).
+    pub remote_header_name: bool,
+    /// Whether `output` was given with `--output` rather than derived
+    /// from the URL.
+    pub explicit_output: bool,
+}
+
+/// Everything wcurl intends to download, resolved from a [`Config`].
+#[derive(Debug, Clone)]
+pub struct DownloadPlan {
+    pub downloads: Vec<Download>,
+    pub curl_options: Vec<String>,
+    pub retries: u32,
+    pub retry_delay: Option<u32>,
+    pub retry_max_time: Option<u32>,
+    pub retry_all_errors: bool,
+    pub retry_connrefused: bool,
+    pub parallel: bool,
+    pub parallel_max: Option<u32>,
+    pub parallel_max_host: Option<u32>,
+    /// Download into `.part` files and resume them (`--continue`).
+    pub resume: bool,
+    /// Rules filenames taken from URLs or `Content-Disposition` follow.
+    pub sanitize: SanitizeProfile,
+    /// Normalize filenames taken from URLs or `Content-Disposition` to NFC.
+    pub normalize_filename: bool,
+    /// What to do when an output path already exists (`--on-conflict`).
+    pub on_conflict: OnConflict,
+    /// What to do when downloads share an output path (`--on-collision`).
+    pub on_collision: OnCollision,
+    /// Compare output paths case-insensitively when looking for collisions.
+    pub ignore_case: bool,
+    /// URLs dropped from `downloads` because they were listed twice or
+    /// their file already exists, for the `--report` file.
+    pub skipped: Vec<Transfer>,
+}
+
+impl DownloadPlan {
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        if config.output_paths.len() > config.urls.len() {
+            return Err(format!(
+                "Got {} --output values for {} URLs",
+                config.output_paths.len(),
+                config.urls.len()
+            ));
+        }
+
+        if config.checksums.len() > config.urls.len() {
+            return Err(format!(
+                "Got {} --checksum values for {} URLs",
+                config.checksums.len(),
+                config.urls.len()
+            ));
+        }
+
+        if config
+            .parallel_max
+            .is_some_and(|max| !(1..=MAX_PARALLEL).contains(&max))
+        {
+            return Err(format!(
+                "--parallel-max must be between 1 and {}",
+                MAX_PARALLEL
+            ));
+        }
+
+        if config.parallel_max_host == Some(0) {
+            return Err("--parallel-max-host must be at least 1".to_string());
+        }
+
+        if config.normalize_filename && !cfg!(feature = "nfc") {
+            return Err(
+                "--normalize-filename is not available: wcurl was built without the 'nfc' feature"
+                    .to_string(),
+            );
+        }
+
+        let downloads = config
+            .urls
+            .iter()
+            .enumerate()
+            .map(|(idx, url)| {
+                let explicit = config.output_paths.get(idx);
+                let output = match explicit {
+                    Some(path) => path.clone(),
+                    None => clean_filename(
+                        &get_url_filename(url, config.decode_filename),
+                        config.normalize_filename,
+                        config.sanitize,
+                    ),
+                };
+                let output = match config.output_dir {
+                    Some(ref dir) if output != "-" => {
+                        Path::new(dir).join(output).to_string_lossy().into_owned()
+                    }
+                    _ => output,
+                };
+
+                Download {
+                    url: url.clone(),
+                    output,
+                    checksum: config.checksums.get(idx).cloned(),
+                    if_range: None,
+                    remote_header_name: config.remote_header_name && explicit.is_none(),
+                    explicit_output: explicit.is_some(),
+                }
+            })
+            .collect::<Vec<Download>>();
+
+        // Downloads to standard output are never staged, so there is no
+        // file to verify before it reaches the reader.
+        for download in downloads.iter().filter(|download| download.output == "-") {
+            if download.checksum.is_some() || config.checksum_file.is_some() {
+                return Err(format!(
+                    "Can't verify the checksum of {}: it is written to standard output",
+                    download.url
+                ));
+            }
+        }
+
+        Ok(DownloadPlan {
+            downloads,
+            curl_options: config.curl_options.clone(),
+            retries: config.retries,
+            retry_delay: config.retry_delay,
+            retry_max_time: config.retry_max_time,
+            retry_all_errors: config.retry_all_errors,
+            retry_connrefused: config.retry_connrefused,
+            parallel: config.parallel,
+            parallel_max: config.parallel_max,
+            parallel_max_host: config.parallel_max_host,
+            resume: config.resume,
+            sanitize: config.sanitize,
+            normalize_filename: config.normalize_filename,
+            on_conflict: config.on_conflict,
+            on_collision: config.on_collision,
+            ignore_case: config.ignore_case,
+            skipped: Vec::new(),
+        })
+    }
+
+    /// Makes sure no two downloads are saved to the same file. A URL listed
+    /// more than once with the same output is downloaded once, and the
+    /// repeats are kept in `skipped`. Other collisions between names
+    /// derived from URLs are resolved with `--on-collision prefix-host` by
+    /// prefixing the host, followed by the URL's directories for URLs on the
+    /// same host, and numbering any names that still clash. Anything left
+    /// over is reported as a table of the colliding URLs.
+    pub fn resolve_collisions(&mut self) -> Result<(), String> {
+        let mut first: HashMap<(String, String), usize> = HashMap::new();
+        let mut duplicates = Vec::new();
+        for download in mem::take(&mut self.downloads) {
+            let key = (
+                download.url.clone(),
+                collision_key(&download, self.ignore_case),
+            );
+            if let Some(&idx) = first.get(&key) {
+                eprintln!(
+                    "Note: {} is listed more than once, downloading it once",
+                    download.url
+                );
+                duplicates.push(idx);
+            } else {
+                first.insert(key, self.downloads.len());
+                self.downloads.push(download);
+            }
+        }
+
+        if self.on_collision == OnCollision::PrefixHost {
+            for group in self.collisions() {
+                let hosts: Vec<String> = group
+                    .iter()
+                    .map(|&idx| url_host(&self.downloads[idx].url).to_lowercase())
+                    .collect();
+                for (&idx, host) in group.iter().zip(&hosts) {
+                    let shared_host = hosts.iter().filter(|&other| other == host).count() > 1;
+                    let download = &mut self.downloads[idx];
+                    if download.explicit_output {
+                        continue;
+                    }
+                    let mut parts = vec![url_host(&download.url)];
+                    if shared_host {
+                        parts.extend(url_dirs(&download.url));
+                    }
+                    let prefix: Vec<String> = parts
+                        .iter()
+                        .map(|part| sanitize_filename(part, self.sanitize))
+                        .collect();
+                    let path = Path::new(&download.output);
+                    let name = path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    let prefixed = format!("{}_{}", prefix.join("_"), name);
+                    download.output = path
+                        .with_file_name(truncate_filename(&prefixed, MAX_FILENAME_BYTES))
+                        .to_string_lossy()
+                        .into_owned();
+                }
+            }
+
+            for group in self.collisions() {
+                for (n, &idx) in group.iter().enumerate().skip(1) {
+                    let download = &mut self.downloads[idx];
+                    if !download.explicit_output {
+                        download.output = staging::numbered_name(&download.output, n as u32);
+                    }
+                }
+            }
+        }
+
+        for idx in duplicates {
+            let download = &self.downloads[idx];
+            self.skipped.push(Transfer::skipped(
+                &download.url,
+                &download.output,
+                Skip::Duplicate,
+            ));
+        }
+
+        let collisions = self.collisions();
+        if collisions.is_empty() {
+            return Ok(());
+        }
+
+        let width = collisions
+            .iter()
+            .map(|group| self.downloads[group[0]].output.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max("OUTPUT".len());
+        let mut table = format!(
+            "Several URLs would be saved to the same file:\n  {:width$}  URL",
+            "OUTPUT"
+        );
+        for group in &collisions {
+            for (n, &idx) in group.iter().enumerate() {
+                let output = if n == 0 {
+                    self.downloads[idx].output.as_str()
+                } else {
+                    ""
+                };
+                table.push_str(&format!(
+                    "\n  {:width$}  {}",
+                    output, self.downloads[idx].url
+                ));
+            }
+        }
+        table.push_str("\nGive each URL its own --output to choose the names.");
+        Err(table)
+    }
+
+    /// Groups of download indices that share an output path, in plan order.
+    fn collisions(&self) -> Vec<Vec<usize>> {
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut by_key: HashMap<String, usize> = HashMap::new();
+
+        for (idx, download) in self.downloads.iter().enumerate() {
+            if download.output == "-" {
+                continue;
+            }
+            match by_key.get(&collision_key(download, self.ignore_case)) {
+                Some(&group) => groups[group].push(idx),
+                None => {
+                    by_key.insert(collision_key(download, self.ignore_case), groups.len());
+                    groups.push(vec![idx]);
+                }
+            }
+        }
+
+        groups.retain(|group| group.len() > 1);
+        groups
+    }
+
+    /// Applies `--on-conflict skip` and `fail` before anything is
+    /// downloaded: existing outputs are dropped from the plan with a note
+    /// and kept in `skipped`, or reported together as an error. Outputs
+    /// named by the server with `--remote-header-name` are only known, and
+    /// checked, afterwards.
+    pub fn check_existing(&mut self) -> Result<(), String> {
+        if !matches!(self.on_conflict, OnConflict::Skip | OnConflict::Fail) {
+            return Ok(());
+        }
+
+        let exists = |download: &Download| {
+            !download.remote_header_name
+                && download.output != "-"
+                && Path::new(&download.output).exists()
+        };
+
+        if self.on_conflict == OnConflict::Fail {
+            let existing: Vec<String> = self
+                .downloads
+                .iter()
+                .filter(|download| exists(download))
+                .map(|download| format!("{} already exists", download.output))
+                .collect();
+            return if existing.is_empty() {
+                Ok(())
+            } else {
+                Err(existing.join("\n"))
+            };
+        }
+
+        let skipped = &mut self.skipped;
+        self.downloads.retain(|download| {
+            let skip = exists(download);
+            if skip {
+                eprintln!("Note: {} already exists, skipping", download.output);
+                skipped.push(Transfer::skipped(
+                    &download.url,
+                    &download.output,
+                    Skip::Existing,
+                ));
+            }
+            !skip
+        });
+        Ok(())
+    }
+
+    /// With `--continue`, looks for `.part` files from earlier runs and
+    /// records how to resume them. Partial files that can't be validated
+    /// against the server are discarded unless `dry_run` is set.
+    ///
+    /// Downloads whose final file already exists are considered done by an
+    /// earlier run and moved from the plan to `skipped`.
+    pub fn prepare_resume(&mut self, dry_run: bool) -> Result<(), String> {
+        if !self.resume {
+            return Ok(());
+        }
+
+        let skipped = &mut self.skipped;
+        self.downloads.retain(|download| {
+            let done = Path::new(&download.output).exists()
+                && !Path::new(&resume::part_path(&download.output)).exists();
+            if done {
+                eprintln!("Note: {} already exists, skipping", download.output);
+                skipped.push(Transfer::skipped(
+                    &download.url,
+                    &download.output,
+                    Skip::Existing,
+                ));
+            }
+            !done
+        });
+
+        for download in &mut self.downloads {
+            load_if_range(download, dry_run)?;
+        }
+
+        Ok(())
+    }
+
+    /// Before retrying the `--continue` downloads at `indices`, picks up
+    /// the validators their `.part` files were last saved with.
+    pub fn reload_resume(&mut self, indices: &[usize]) -> Result<(), String> {
+        if !self.resume {
+            return Ok(());
+        }
+
+        for &idx in indices {
+            load_if_range(&mut self.downloads[idx], false)?;
+        }
+        Ok(())
+    }
+
+    /// The index of every download, in plan order.
+    fn indices(&self) -> Vec<usize> {
+        (0..self.downloads.len()).collect()
+    }
+
+    /// Where the `idx`th download is written until it is complete: its
+    /// `.part` file with `--continue`, otherwise a temporary file next to
+    /// the output. Downloads to standard output aren't staged.
+    pub fn staging_path(&self, idx: usize) -> Option<String> {
+        let output = &self.downloads[idx].output;
+        if output == "-" {
+            None
+        } else if self.resume {
+            Some(resume::part_path(output))
+        } else {
+            Some(staging::temp_path(output, idx))
+        }
+    }
+
+    /// Creates the temporary file of every download exclusively before
+    /// anything is downloaded, so curl only ever writes to files this run
+    /// owns and never over an existing one. `.part` files are left alone.
+    pub fn reserve_staging(&self) -> Result<(), String> {
+        if self.resume {
+            return Ok(());
+        }
+
+        for idx in self.indices() {
+            if let Some(temp) = self.staging_path(idx) {
+                if let Err(e) = staging::reserve(&temp) {
+                    self.release_staging(idx)?;
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the temporary files of the first `count` downloads, after a
+    /// run that couldn't start.
+    pub fn release_staging(&self, count: usize) -> Result<(), String> {
+        if self.resume {
+            return Ok(());
+        }
+
+        for idx in 0..count {
+            if let Some(temp) = self.staging_path(idx) {
+                staging::remove(&temp)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Where the response headers of the `idx`th download are saved, when
+    /// they are needed after the transfer: to resume it with `--continue`
+    /// or to name it with `--remote-header-name`.
+    pub fn headers_path(&self, idx: usize) -> Option<String> {
+        if self.resume || self.downloads[idx].remote_header_name {
+            self.staging_path(idx)
+                .map(|staging| format!("{}.headers", staging))
+        } else {
+            None
+        }
+    }
+
+    /// Runs curl for this plan and returns how each download went.
+    ///
+    /// curl 7.75.0 and later describe each transfer, exit code included,
+    /// with `%{json}`. Older curls only report how a whole run went, so
+    /// wcurl runs one of them per download.
+    pub fn run_curl(&self, curl: &CurlInfo) -> Result<Vec<Transfer>, String> {
+        self.run_curl_for(curl, &self.indices())
+    }
+
+    /// Like [`DownloadPlan::run_curl`], for only the downloads at `indices`.
+    /// Transfers are returned in the same order.
+    pub fn run_curl_for(
+        &self,
+        curl: &CurlInfo,
+        indices: &[usize],
+    ) -> Result<Vec<Transfer>, String> {
+        let (files, streams) = self.split_streams(indices);
+        let mut transfers: HashMap<usize, Transfer> = HashMap::new();
+
+        for &idx in &streams {
+            let invocation = self.curl_invocation_for(curl, &[idx]);
+            transfers.extend(iter::once(idx).zip(self.run_invocation(
+                curl,
+                &invocation,
+                &[idx],
+            )?));
+        }
+
+        if self.runs_children(curl, &files) {
+            transfers.extend(files.iter().copied().zip(self.run_children(curl, &files)?));
+        } else {
+            for batch in self.batches(curl, &files) {
+                let invocation = self.curl_invocation_for(curl, &batch);
+                transfers.extend(batch.iter().copied().zip(self.run_invocation(
+                    curl,
+                    &invocation,
+                    &batch,
+                )?));
+            }
+        }
+
+        Ok(indices
+            .iter()
+            .map(|&idx| {
+                transfers
+                    .remove(&idx)
+                    .unwrap_or_else(|| self.missing_transfer(idx))
+            })
+            .collect())
+    }
+
+    /// Separates the downloads at `indices` that are written to files from
+    /// those written to standard output. Each of the latter gets a curl of
+    /// its own, with wcurl's standard output, so it can't mix with another
+    /// download or with `--write-out`.
+    fn split_streams(&self, indices: &[usize]) -> (Vec<usize>, Vec<usize>) {
+        indices
+            .iter()
+            .partition(|&&idx| self.staging_path(idx).is_some())
+    }
+
+    /// Splits the downloads at `indices` into the groups wcurl runs curl
+    /// for, one group after another. Everything is left to a single curl
+    /// unless its command line would exceed [`MAX_COMMAND_LINE`]; a download
+    /// whose arguments alone exceed it gets a group of its own.
+    fn batches(&self, curl: &CurlInfo, indices: &[usize]) -> Vec<Vec<usize>> {
+        let mut groups = Vec::new();
+        let mut group = Vec::new();
+        let mut length = 0;
+
+        for &idx in indices {
+            let cost = command_line_len(self.curl_invocation_for(curl, &[idx]).get_args())
+                + command_line_len(&["--next"]);
+            if !group.is_empty() && length + cost > MAX_COMMAND_LINE {
+                groups.push(mem::take(&mut group));
+                length = 0;
+            }
+            length += cost;
+            group.push(idx);
+        }
+
+        if !group.is_empty() {
+            groups.push(group);
+        }
+        groups
+    }
+
+    /// The curl command lines [`DownloadPlan::run_curl`] runs, in order,
+    /// or all at once when wcurl runs them in parallel itself.
+    pub fn curl_invocations(&self, curl: &CurlInfo) -> Vec<CurlInvocation> {
+        let (files, streams) = self.split_streams(&self.indices());
+        let mut invocations: Vec<CurlInvocation> = streams
+            .iter()
+            .map(|&idx| self.curl_invocation_for(curl, &[idx]))
+            .collect();
+
+        if self.runs_children(curl, &files) {
+            invocations.extend(files.iter().map(|&idx| self.child_invocation(curl, idx)));
+        } else {
+            invocations.extend(
+                self.batches(curl, &files)
+                    .iter()
+                    .map(|batch| self.curl_invocation_for(curl, batch)),
+            );
+        }
+        invocations
+    }
+
+    /// Whether wcurl runs a curl per download itself: because the installed
+    /// curl can't report the exit code of each transfer, which includes
+    /// every curl without `--parallel`, or is too old for
+    /// `--parallel-max-host` when that was given.
+    fn runs_children(&self, curl: &CurlInfo, indices: &[usize]) -> bool {
+        indices.len() >= 2
+            && (!curl.supports(Capability::WriteOutExitCode)
+                || !self.writes_out(curl)
+                || (self.parallel
+                    && self.parallel_max_host.is_some()
+                    && !curl.supports(Capability::ParallelMaxHost)))
+    }
+
+    /// Whether wcurl asks curl to describe each transfer with
+    /// `--write-out '%{json}'`: curl 7.70.0 and later do, unless the curl
+    /// options bring a `--write-out` of their own, which would replace it.
+    fn writes_out(&self, curl: &CurlInfo) -> bool {
+        curl.supports(Capability::WriteOutJson)
+            && !self.curl_options.iter().any(|option| is_write_out(option))
+    }
+
+    /// The command line for one of the curls run by
+    /// [`DownloadPlan::run_children`]. When they run in parallel their
+    /// progress meters are turned off, as they would overwrite each other,
+    /// but errors are still shown.
+    fn child_invocation(&self, curl: &CurlInfo, idx: usize) -> CurlInvocation {
+        let mut invocation = self.curl_invocation_for(curl, &[idx]);
+        if self.parallel {
+            invocation.args(["--silent", "--show-error"]);
+        }
+        invocation
+    }
+
+    /// Runs a curl per download at `indices`, at most `--parallel-max` of
+    /// them at once (one with `--sequential`) and, when given,
+    /// `--parallel-max-host` for any one host, and returns how each
+    /// download went, in order. Each transfer gets the exit code of its own
+    /// curl.
+    fn run_children(&self, curl: &CurlInfo, indices: &[usize]) -> Result<Vec<Transfer>, String> {
+        let workers = if self.parallel {
+            self.parallel_max
+                .unwrap_or(DEFAULT_PARALLEL_MAX)
+                .min(u32::try_from(indices.len()).unwrap_or(u32::MAX))
+        } else {
+            1
+        };
+        let per_host = self
+            .parallel_max_host
+            .map_or(usize::MAX, |max| max as usize);
+        let host = |idx: usize| url_host(&self.downloads[idx].url).to_lowercase();
+
+        let state = Mutex::new(Children {
+            pending: indices.iter().copied().collect(),
+            active: HashMap::new(),
+            done: HashMap::new(),
+        });
+        let changed = Condvar::new();
+
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    let idx = {
+                        let mut children = state.lock().unwrap();
+                        loop {
+                            if children.pending.is_empty() {
+                                return;
+                            }
+                            let free = children.pending.iter().position(|&idx| {
+                                children.active.get(&host(idx)).copied().unwrap_or(0) < per_host
+                            });
+                            if let Some(pos) = free {
+                                let idx = children.pending.remove(pos).unwrap();
+                                *children.active.entry(host(idx)).or_insert(0) += 1;
+                                break idx;
+                            }
+                            children = changed.wait(children).unwrap();
+                        }
+                    };
+
+                    let started = Instant::now();
+                    let invocation = self.child_invocation(curl, idx);
+                    let result =
+                        self.run_invocation(curl, &invocation, &[idx])
+                            .map(|mut transfers| {
+                                let mut transfer = transfers.remove(0);
+                                transfer
+                                    .duration
+                                    .get_or_insert(started.elapsed().as_secs_f64());
+                                transfer
+                            });
+
+                    let mut children = state.lock().unwrap();
+                    if let Some(active) = children.active.get_mut(&host(idx)) {
+                        *active -= 1;
+                    }
+                    children.done.insert(idx, result);
+                    changed.notify_all();
+                });
+            }
+        });
+
+        let mut done = state.into_inner().unwrap().done;
+        indices
+            .iter()
+            .map(|idx| {
+                done.remove(idx)
+                    .unwrap_or_else(|| Ok(self.missing_transfer(*idx)))
+            })
+            .collect()
+    }
+
+    /// A failed transfer for the `idx`th download, for when no curl run
+    /// accounted for it, so its temporary file is never taken as finished.
+    fn missing_transfer(&self, idx: usize) -> Transfer {
+        let mut transfer = Transfer::new(&self.downloads[idx].url);
+        transfer.error = Some("curl never ran this download".to_string());
+        transfer
+    }
+
+    /// Runs `invocation`, which downloads the downloads at `indices`, and
+    /// returns how each of them went.
+    fn run_invocation(
+        &self,
+        curl: &CurlInfo,
+        invocation: &CurlInvocation,
+        indices: &[usize],
+    ) -> Result<Vec<Transfer>, String> {
+        let writes_out = self.writes_out(curl);
+        let capture = writes_out && indices.iter().all(|&idx| self.staging_path(idx).is_some());
+        let (status, stdout) = if capture {
+            invocation.run_output()?
+        } else {
+            (invocation.run_status()?, Vec::new())
+        };
+        let overall = if status.success() {
+            None
+        } else {
+            Some(exit_error(status))
+        };
+
+        let mut write_outs = HashMap::new();
+        if writes_out {
+            for line in String::from_utf8_lossy(&stdout).lines() {
+                let info = match json::parse(line) {
+                    Ok(info) => info,
+                    Err(_) => continue,
+                };
+                if let Some(path) = info.get("filename_effective").and_then(json::Value::as_str) {
+                    write_outs.insert(path.to_string(), info.clone());
+                }
+            }
+        }
+
+        let transfers = indices
+            .iter()
+            .map(|&idx| {
+                let mut transfer = Transfer::new(&self.downloads[idx].url);
+                let info = self
+                    .staging_path(idx)
+                    .and_then(|path| write_outs.get(&path));
+                if let Some(info) = info {
+                    transfer.apply_write_out(info);
+                }
+                if transfer.exit_code.is_none() {
+                    // A curl that ran several downloads, succeeded and
+                    // described some of them says nothing about one it left
+                    // out, so that one isn't taken as written.
+                    if indices.len() == 1 || !status.success() || write_outs.is_empty() {
+                        transfer.exit_code =
+                            status.code().and_then(|code| u32::try_from(code).ok());
+                        transfer.error = overall.clone();
+                    } else {
+                        transfer.error =
+                            Some("curl didn't report how this download went".to_string());
+                    }
+                }
+                transfer
+            })
+            .collect();
+
+        Ok(transfers)
+    }
+
+    /// After a failed `--continue` run, finds the downloads whose server
+    /// ignored the range request because the remote file changed. Their
+    /// partial files are discarded so they can be fetched again from the
+    /// start, and their indices are returned.
+    pub fn resume_restarts(&self, transfers: &[Transfer]) -> Result<Vec<usize>, String> {
+        let mut restart = Vec::new();
+
+        for (idx, (download, transfer)) in self.downloads.iter().zip(transfers).enumerate() {
+            if transfer.succeeded() || download.if_range.is_none() {
+                continue;
+            }
+            if let Some(partial) = PartialDownload::find(&download.output) {
+                if !partial.is_complete() && partial.status == Some(200) {
+                    resume::discard(&download.output)?;
+                    restart.push(idx);
+                }
+            }
+        }
+
+        Ok(restart)
+    }
+
+    /// Finishes a run given how each download went: successful downloads
+    /// are checked against their expected checksum and renamed to their
+    /// output path, and the temporary files of failed ones are removed.
+    /// `.part` files of failed `--continue` downloads are kept for the next
+    /// run. Each transfer's `output` is set to where its file ended up, and
+    /// errors found here are recorded in it too.
+    ///
+    /// With a `manifest`, downloads without an explicit `--checksum` are
+    /// looked up in it by output name. A download whose checksum doesn't
+    /// match is moved to `<output>.corrupt` instead. Existing files are
+    /// handled according to `--on-conflict`. All errors are reported at once.
+    pub fn complete(
+        &self,
+        transfers: &mut [Transfer],
+        manifest: Option<&ChecksumManifest>,
+    ) -> Result<(), String> {
+        let mut errors: Vec<String> = Vec::new();
+
+        for (idx, (download, transfer)) in self.downloads.iter().zip(transfers).enumerate() {
+            let complete_part = !transfer.succeeded()
+                && self.resume
+                && PartialDownload::find(&download.output)
+                    .is_some_and(|partial| partial.is_complete());
+            if complete_part {
+                transfer.error = None;
+            }
+
+            if let Some(ref e) = transfer.error {
+                errors.push(format!("{}: {}", download.url, e));
+            }
+
+            let staging = match self.staging_path(idx) {
+                Some(staging) => staging,
+                None => {
+                    if transfer.succeeded() {
+                        transfer.output = Some(download.output.clone());
+                    }
+                    continue;
+                }
+            };
+
+            if !transfer.succeeded() {
+                if !self.resume {
+                    let headers = self.headers_path(idx);
+                    for path in [Some(staging), headers].into_iter().flatten() {
+                        if let Err(e) = staging::remove(&path) {
+                            errors.push(e);
+                        }
+                    }
+                }
+                continue;
+            }
+
+            if let Err(e) = self.finish_download(idx, &staging, manifest, transfer) {
+                transfer.error.get_or_insert_with(|| e.clone());
+                errors.push(e);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+
+    /// Verifies and moves one finished download into place, recording where
+    /// it ended up in `transfer`.
+    fn finish_download(
+        &self,
+        idx: usize,
+        staging: &str,
+        manifest: Option<&ChecksumManifest>,
+        transfer: &mut Transfer,
+    ) -> Result<(), String> {
+        let download = &self.downloads[idx];
+        let headers = self.headers_path(idx);
+        let output = match headers {
+            Some(ref headers) if download.remote_header_name => {
+                self.remote_header_output(download, headers)
+            }
+            _ => download.output.clone(),
+        };
+
+        let mut missing = None;
+        let checksum = match (&download.checksum, manifest) {
+            (Some(checksum), _) => Some(checksum),
+            (None, Some(manifest)) => {
+                let checksum = manifest.lookup(&output);
+                if checksum.is_none() {
+                    missing = Some(format!("{} is missing from the checksum file", output));
+                }
+                checksum
+            }
+            (None, None) => None,
+        };
+
+        let verified = match checksum {
+            Some(checksum) => checksum.verify(staging, &output),
+            None => Ok(()),
+        };
+
+        let result = match verified {
+            Ok(()) => staging::commit(staging, &output, self.on_conflict),
+            Err(e) => {
+                let corrupt = staging::quarantine(staging, &output)?;
+                Err(format!("{} (moved to {})", e, corrupt))
+            }
+        };
+
+        if let Some(headers) = headers {
+            staging::remove(&headers)?;
+        }
+
+        match result? {
+            Some(path) => transfer.output = Some(path),
+            None => {
+                transfer.output = Some(output);
+                transfer.skipped = Some(Skip::Existing);
+            }
+        }
+        missing.map_or(Ok(()), Err)
+    }
+
+    /// The output path for a `--remote-header-name` download: the output's
+    /// directory joined with the `Content-Disposition` filename from the saved
+    /// response headers, or the URL-derived output when there is none or it
+    /// isn't safe to use.
+    fn remote_header_output(&self, download: &Download, headers: &str) -> String {
+        let disposition = resume::read_headers(headers).and_then(|(_, headers)| {
+            resume::header(&headers, "Content-Disposition").map(String::from)
+        });
+        let suggested = match disposition.and_then(|value| content_disposition_filename(&value)) {
+            Some(suggested) => suggested,
+            None => return download.output.clone(),
+        };
+
+        match sanitize_remote_filename(&suggested) {
+            Some(name) => Path::new(&download.output)
+                .with_file_name(clean_filename(
+                    &name,
+                    self.normalize_filename,
+                    self.sanitize,
+                ))
+                .to_string_lossy()
+                .into_owned(),
+            None => {
+                eprintln!(
+                    "Warning: Ignoring unsafe Content-Disposition filename {:?} for {}",
+                    suggested, download.url
+                );
+                download.output.clone()
+            }
+        }
+    }
+
+    /// Builds the curl command line for this plan, given what is known
+    /// about the curl that will run it.
+    pub fn curl_invocation(&self, curl: &CurlInfo) -> CurlInvocation {
+        self.curl_invocation_for(curl, &self.indices())
+    }
+
+    /// Builds the curl command line for only the downloads at `indices`,
+    /// which keep the staging files they have in the whole plan.
+    pub fn curl_invocation_for(&self, curl: &CurlInfo, indices: &[usize]) -> CurlInvocation {
+        let mut invocation = CurlInvocation::new(&curl.program);
+
+        if self.parallel && indices.len() >= 2 && curl.supports(Capability::Parallel) {
+            invocation.arg("--parallel");
+            if let Some(max) = self.parallel_max {
+                invocation.arg("--parallel-max").arg(max.to_string());
+            }
+            if curl.supports(Capability::ParallelMaxHost) {
+                invocation.arg("--parallel-max-host").arg(
+                    self.parallel_max_host
+                        .unwrap_or(DEFAULT_PARALLEL_MAX_HOST)
+                        .to_string(),
+                );
+            }
+        }
+
+        let use_write_out = self.writes_out(curl);
+        let use_retry_connrefused = curl.supports(Capability::RetryConnrefused);
+        let use_retry_all_errors = curl.supports(Capability::RetryAllErrors);
+
+        for (n, &idx) in indices.iter().enumerate() {
+            let download = &self.downloads[idx];
+            if n > 0 {
+                invocation.arg("--next");
+            }
+
+            invocation.args(PER_URL_PARAMS);
+            invocation.arg("--retry").arg(self.retries.to_string());
+            if let Some(delay) = self.retry_delay {
+                invocation.arg("--retry-delay").arg(delay.to_string());
+            }
+            if let Some(max_time) = self.retry_max_time {
+                invocation.arg("--retry-max-time").arg(max_time.to_string());
+            }
+            if self.retry_connrefused && use_retry_connrefused {
+                invocation.arg("--retry-connrefused");
+            }
+            if self.retry_all_errors && use_retry_all_errors {
+                invocation.arg("--retry-all-errors");
+            }
+
+            let staging = self.staging_path(idx);
+            if let Some(headers) = self.headers_path(idx) {
+                invocation.arg("--dump-header").arg(headers);
+            }
+            if staging.is_some() && use_write_out {
+                invocation.arg("--write-out").arg("%{json}\\n");
+            }
+
+            match staging {
+                Some(part) if self.resume => {
+                    invocation
+                        .arg("--output")
+                        .arg(part)
+                        .args(["--continue-at", "-"]);
+                    if let Some(ref validator) = download.if_range {
+                        invocation
+                            .arg("--header")
+                            .arg(format!("If-Range: {}", validator));
+                    }
+                }
+                Some(temp) => {
+                    invocation.arg("--output").arg(temp);
+                }
+                None => {
+                    invocation.arg("--output").arg(&download.output);
+                }
+            }
+
+            invocation.args(&self.curl_options);
+
+            invocation.arg(&download.url);
+        }
+
+        invocation
+    }
+}
+
+/// The program and argument list for one run of curl.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CurlInvocation {
+    program: String,
+    args: Vec<String>,
+}
+
+impl CurlInvocation {
+    pub fn new(program: &str) -> Self {
+        CurlInvocation {
+            program: program.to_string(),
+            args: Vec::new(),
+        }
+    }
+
+    pub fn arg<S: AsRef<str>>(&mut self, arg: S) -> &mut Self {
+        self.args.push(arg.as_ref().to_string());
+        self
+    }
+
+    pub fn args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for arg in args {
+            self.arg(arg);
+        }
+        self
+    }
+
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    pub fn get_args(&self) -> &[String] {
+        &self.args
+    }
+
+    pub fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args);
+        command
+    }
+
+    /// Runs curl with its standard output captured and returns it.
+    pub fn run_capture(&self) -> Result<Vec<u8>, String> {
+        let output = self
+            .command()
+            .stderr(Stdio::inherit())
+            .output()
+            .map_err(|e| format!("Failed to execute curl: {}", e))?;
+
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            Err(exit_error(output.status))
+        }
+    }
+
+    /// Runs curl with its standard output captured and returns its exit
+    /// status along with the output, whether or not it succeeded.
+    pub fn run_output(&self) -> Result<(ExitStatus, Vec<u8>), String> {
+        let output = self
+            .command()
+            .stderr(Stdio::inherit())
+            .output()
+            .map_err(|e| format!("Failed to execute curl: {}", e))?;
+
+        Ok((output.status, output.stdout))
+    }
+
+    /// Runs curl with wcurl's standard output and returns its exit status,
+    /// whether or not it succeeded.
+    pub fn run_status(&self) -> Result<ExitStatus, String> {
+        self.command()
+            .status()
+            .map_err(|e| format!("Failed to execute curl: {}", e))
+    }
+
+    /// Runs curl and waits for it to finish.
+    pub fn run(&self) -> Result<(), String> {
+        let status = self
+            .command()
+            .status()
+            .map_err(|e| format!("Failed to execute curl: {}", e))?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(exit_error(status))
+        }
+    }
+}
+
+impl Default for CurlInvocation {
+    fn default() -> Self {
+        CurlInvocation::new("curl")
+    }
+}
+
+impl fmt::Display for CurlInvocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", shell::quote(&self.program))?;
+        for arg in &self.args {
+            write!(f, " {}", shell::quote(arg))?;
+        }
+        Ok(())
+    }
+}
+
+/// The error for a curl that exited with `status`, worded like the one
+/// for a failed transfer described by `%{json}`.
+fn exit_error(status: ExitStatus) -> String {
+    match status.code() {
+        Some(code) => format!("curl failed with exit code {}", code),
+        None => format!("curl was stopped ({})", status),
+    }
+}
+
+/// Whether the curl option `option` is `--write-out` or `-w`, including
+/// `-w` with its format attached or after other short options, as in `-sw`.
+fn is_write_out(option: &str) -> bool {
+    option == "--write-out"
+        || (option.starts_with('-')
+            && !option.starts_with("--")
+            && option[1..]
+                .find('w')
+                .is_some_and(|w| option[1..w + 1].chars().all(|c| c.is_ascii_alphabetic())))
+}
+
+/// Sets the `If-Range` validator for resuming `download` from its `.part`
+/// file, discarding a partial file that has none unless `dry_run` is set.
+fn load_if_range(download: &mut Download, dry_run: bool) -> Result<(), String> {
+    download.if_range = None;
+    if let Some(partial) = PartialDownload::find(&download.output) {
+        match partial.validator {
+            Some(validator) => download.if_range = Some(validator),
+            None if !dry_run => resume::discard(&download.output)?,
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/// Roughly how much of the command line `args` take up, counting a
+/// separator and possible quotes for each.
+fn command_line_len<S: AsRef<str>>(args: &[S]) -> usize {
+    args.iter().map(|arg| arg.as_ref().len() + 3).sum()
+}
+
+/// Applies `--normalize-filename` and `--sanitize` to a derived filename.
+fn clean_filename(name: &str, normalize: bool, profile: SanitizeProfile) -> String {
+    if normalize {
+        sanitize_filename(&normalize_filename(name), profile)
+    } else {
+        sanitize_filename(name, profile)
+    }
+}
+
+/// The output path as compared when looking for collisions.
+fn collision_key(download: &Download, ignore_case: bool) -> String {
+    if ignore_case {
+        download.output.to_lowercase()
+    } else {
+        download.output.clone()
+    }
+}
+
+/// The directories of `url`'s path, leaving out its last segment.
+fn url_dirs(url: &str) -> Vec<String> {
+    let rest = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    let path = rest.split(['?', '#']).next().unwrap_or(rest);
+    let mut segments: Vec<&str> = path.split('/').skip(1).collect();
+    segments.pop();
+    segments
+        .into_iter()
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// The host (and port) of `url`, without any user information.
+fn url_host(url: &str) -> String {
+    let rest = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or(rest);
+    let host = authority
+        .rsplit_once('@')
+        .map(|(_, host)| host)
+        .unwrap_or(authority);
+    host.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curl::CurlVersion;
+
+    fn plan(urls: &[&str], outputs: &[&str]) -> DownloadPlan {
+        let mut config = Config::new();
+        config.urls = urls.iter().map(|url| url.to_string()).collect();
+        config.output_paths = outputs.iter().map(|output| output.to_string()).collect();
+        DownloadPlan::from_config(&config).unwrap()
+    }
+
+    fn outputs(plan: &DownloadPlan) -> Vec<&str> {
+        plan.downloads
+            .iter()
+            .map(|download| download.output.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn output_dir_leaves_stdout_alone() {
+        let mut config = Config::new();
+        config.urls = vec!["http://h/a.txt".to_string(), "http://h/b.txt".to_string()];
+        config.output_paths = vec!["-".to_string()];
+        config.output_dir = Some("d".to_string());
+        let plan = DownloadPlan::from_config(&config).unwrap();
+
+        let joined = Path::new("d").join("b.txt");
+        assert_eq!(outputs(&plan), ["-", joined.to_str().unwrap()]);
+        assert_eq!(plan.staging_path(0), None);
+    }
+
+    #[test]
+    fn checksums_need_a_file() {
+        let mut config = Config::new();
+        config.urls = vec!["http://h/a.txt".to_string()];
+        config.output_paths = vec!["-".to_string()];
+        assert!(DownloadPlan::from_config(&config).is_ok());
+
+        config.checksum_file = Some("SHA256SUMS".to_string());
+        assert!(DownloadPlan::from_config(&config).is_err());
+
+        config.checksum_file = None;
+        config.checksums = vec![
+            "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+                .parse()
+                .unwrap(),
+        ];
+        let error = DownloadPlan::from_config(&config).unwrap_err();
+        assert!(error.contains("standard output"), "{}", error);
+    }
+
+    #[test]
+    fn collisions_get_the_host_as_prefix() {
+        let mut plan = plan(&["http://a/latest.txt", "http://b/latest.txt"], &[]);
+        plan.resolve_collisions().unwrap();
+        assert_eq!(outputs(&plan), ["a_latest.txt", "b_latest.txt"]);
+    }
+
+    #[test]
+    fn collisions_on_one_host_get_its_directories_as_prefix() {
+        let mut plan = plan(
+            &[
+                "http://h/x/latest.txt",
+                "http://h/y/latest.txt",
+                "http://g/latest.txt",
+            ],
+            &[],
+        );
+        plan.resolve_collisions().unwrap();
+        assert_eq!(
+            outputs(&plan),
+            ["h_x_latest.txt", "h_y_latest.txt", "g_latest.txt"]
+        );
+    }
+
+    #[test]
+    fn remaining_collisions_are_numbered() {
+        let mut plan = plan(&["http://h/x/a.txt?v=1", "http://h/x/a.txt?v=2"], &[]);
+        plan.resolve_collisions().unwrap();
+        assert_eq!(outputs(&plan), ["h_x_a.txt", "h_x_a (1).txt"]);
+    }
+
+    #[test]
+    fn resolved_names_stay_within_name_max() {
+        let name = format!("{}.txt", "n".repeat(251));
+        let a = format!("http://h/x/{}", name);
+        let b = format!("http://h/y/{}", name);
+        let mut plan = plan(&[&a, &b, &format!("{}?v=2", b)], &[]);
+        plan.resolve_collisions().unwrap();
+
+        let outputs = outputs(&plan);
+        assert!(outputs[0].starts_with("h_x_nnn"));
+        assert!(outputs[1].starts_with("h_y_nnn"));
+        assert!(outputs[2].ends_with(" (1).txt"));
+        for output in outputs {
+            assert!(output.len() <= MAX_FILENAME_BYTES, "{}", output);
+            assert!(output.ends_with(".txt"));
+        }
+    }
+
+    #[test]
+    fn repeated_urls_are_downloaded_once() {
+        let mut plan = plan(&["http://h/a.txt", "http://g/a.txt", "http://h/a.txt"], &[]);
+        plan.resolve_collisions().unwrap();
+        assert_eq!(outputs(&plan), ["h_a.txt", "g_a.txt"]);
+        assert_eq!(
+            plan.skipped,
+            [Transfer::skipped(
+                "http://h/a.txt",
+                "h_a.txt",
+                Skip::Duplicate
+            )]
+        );
+    }
+
+    #[test]
+    fn explicit_outputs_are_never_renamed() {
+        let mut plan = plan(
+            &["http://h/a.txt", "http://g/b.txt"],
+            &["out.txt", "out.txt"],
+        );
+        let table = plan.resolve_collisions().unwrap_err();
+        assert!(table.contains("http://h/a.txt"));
+        assert!(table.contains("http://g/b.txt"));
+        assert_eq!(outputs(&plan), ["out.txt", "out.txt"]);
+    }
+
+    #[test]
+    fn abort_leaves_names_alone() {
+        let mut plan = plan(&["http://h/x/a.txt", "http://g/a.txt"], &[]);
+        plan.on_collision = OnCollision::Abort;
+        assert!(plan.resolve_collisions().is_err());
+        assert_eq!(outputs(&plan), ["a.txt", "a.txt"]);
+    }
+
+    fn curl(major: u32, minor: u32, patch: u32) -> CurlInfo {
+        CurlInfo::with_version(CurlVersion::new(major, minor, patch))
+    }
+
+    fn argv(invocation: &CurlInvocation) -> Vec<&str> {
+        invocation.get_args().iter().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn invocation_for_one_url() {
+        let plan = plan(&["https://example.com/a.txt"], &[]);
+        let invocation = plan.curl_invocation(&curl(8, 5, 0));
+        let temp = staging::temp_path("a.txt", 0);
+
+        let mut expected: Vec<&str> = PER_URL_PARAMS.to_vec();
+        expected.extend([
+            "--retry",
+            "5",
+            "--write-out",
+            "%{json}\\n",
+            "--output",
+            &temp,
+            "https://example.com/a.txt",
+        ]);
+        assert_eq!(invocation.program(), "curl");
+        assert_eq!(argv(&invocation), expected);
+    }
+
+    #[test]
+    fn invocation_for_several_urls() {
+        let mut plan = plan(&["http://h/a.txt", "http://h/b.txt"], &[]);
+        plan.curl_options = vec!["--insecure".to_string()];
+
+        let invocation = plan.curl_invocation(&curl(8, 5, 0));
+        let args = argv(&invocation);
+        assert_eq!(args[0], "--parallel");
+        assert!(!args.contains(&"--parallel-max-host"));
+        assert_eq!(args.iter().filter(|&&arg| arg == "--next").count(), 1);
+        assert_eq!(
+            args.iter().filter(|&&arg| arg == "--insecure").count(),
+            2,
+            "every URL gets the curl options"
+        );
+        assert_eq!(args.last(), Some(&"http://h/b.txt"));
+
+        let invocation = plan.curl_invocation(&curl(8, 16, 0));
+        assert_eq!(
+            argv(&invocation)[..3],
+            ["--parallel", "--parallel-max-host", "5"]
+        );
+
+        plan.parallel = false;
+        let invocation = plan.curl_invocation(&curl(8, 16, 0));
+        assert!(!argv(&invocation).contains(&"--parallel"));
+    }
+
+    #[test]
+    fn invocation_leaves_out_what_old_curls_lack() {
+        let mut plan = plan(&["http://h/a.txt"], &[]);
+        plan.retry_connrefused = true;
+        plan.retry_all_errors = true;
+
+        let invocation = plan.curl_invocation(&curl(7, 50, 0));
+        let args = argv(&invocation);
+        assert!(!args.contains(&"--write-out"));
+        assert!(!args.contains(&"--retry-connrefused"));
+        assert!(!args.contains(&"--retry-all-errors"));
+
+        let invocation = plan.curl_invocation(&curl(7, 71, 0));
+        let args = argv(&invocation);
+        assert!(args.contains(&"--write-out"));
+        assert!(args.contains(&"--retry-connrefused"));
+        assert!(args.contains(&"--retry-all-errors"));
+    }
+
+    #[test]
+    fn invocation_leaves_write_out_to_the_user() {
+        let mut plan = plan(&["http://h/a.txt"], &[]);
+        plan.curl_options = vec!["-w".to_string(), "%{http_code}\\n".to_string()];
+        let invocation = plan.curl_invocation(&curl(8, 5, 0));
+        let args = argv(&invocation);
+        assert!(!args.contains(&"--write-out"));
+        assert_eq!(args.iter().filter(|&&arg| arg == "-w").count(), 1);
+
+        for option in ["-w", "--write-out", "-w%{json}", "-sSw"] {
+            assert!(is_write_out(option), "{}", option);
+        }
+        for option in ["-s", "--wrong", "http://w/", "-#"] {
+            assert!(!is_write_out(option), "{}", option);
+        }
+    }
+
+    #[test]
+    fn invocation_writes_to_stdout_without_staging() {
+        let plan = plan(&["http://h/a.txt"], &["-"]);
+        let invocation = plan.curl_invocation(&curl(8, 5, 0));
+        let args = argv(&invocation);
+        assert!(args.windows(2).any(|pair| pair == ["--output", "-"]));
+        assert!(!args.contains(&"--write-out"));
+    }
+
+    #[test]
+    fn invocation_resumes_part_files() {
+        let mut plan = plan(&["http://h/a.txt"], &[]);
+        plan.resume = true;
+        let invocation = plan.curl_invocation(&curl(8, 5, 0));
+        let args = argv(&invocation);
+        assert!(args
+            .windows(4)
+            .any(|window| window == ["--output", "a.txt.part", "--continue-at", "-"]));
+        assert!(args.windows(2).any(|pair| pair[0] == "--dump-header"));
+    }
+}
@@ -0,0 +1,174 @@
+//! The `--report` file: one JSON object per URL describing how its transfer
+//! went, written as a JSON array or as newline-delimited JSON.
+
+use std::fs;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::json::{self, Value};
+
+/// How the `--report` file is laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// A single JSON array.
+    Json,
+    /// One JSON object per line.
+    Ndjson,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(ReportFormat::Json),
+            "ndjson" => Ok(ReportFormat::Ndjson),
+            _ => Err(format!(
+                "Unknown report format '{}': expected json or ndjson",
+                s
+            )),
+        }
+    }
+}
+
+/// Why a URL was left out of the downloads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Skip {
+    /// Its output already existed (`--on-conflict skip`, or a finished
+    /// download with `--continue`).
+    Existing,
+    /// The same URL was listed earlier with the same output.
+    Duplicate,
+}
+
+/// What happened to one download. Fields the backend couldn't tell are
+/// `None`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Transfer {
+    pub url: String,
+    /// Where the file ended up once moved into place, or the file that
+    /// was already there for a skipped URL.
+    pub output: Option<String>,
+    pub http_status: Option<u16>,
+    /// The URL the file was finally fetched from, after redirects.
+    pub effective_url: Option<String>,
+    pub bytes: Option<u64>,
+    /// Seconds the transfer took.
+    pub duration: Option<f64>,
+    pub content_type: Option<String>,
+    pub exit_code: Option<u32>,
+    pub error: Option<String>,
+    /// Set when the URL wasn't downloaded at all.
+    pub skipped: Option<Skip>,
+}
+
+impl Transfer {
+    pub fn new(url: &str) -> Self {
+        Transfer {
+            url: url.to_string(),
+            ..Transfer::default()
+        }
+    }
+
+    /// A URL that wasn't downloaded, whose file is at `output`.
+    pub fn skipped(url: &str, output: &str, skip: Skip) -> Self {
+        Transfer {
+            url: url.to_string(),
+            output: Some(output.to_string()),
+            skipped: Some(skip),
+            ..Transfer::default()
+        }
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Fills in the details curl printed for `--write-out '%{json}'`.
+    pub fn apply_write_out(&mut self, info: &Value) {
+        let string = |key: &str| {
+            info.get(key)
+                .and_then(Value::as_str)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+        };
+
+        self.http_status = info
+            .get("http_code")
+            .and_then(Value::as_u64)
+            .filter(|&code| code != 0)
+            .and_then(|code| u16::try_from(code).ok());
+        self.effective_url = string("url_effective");
+        self.bytes = info.get("size_download").and_then(Value::as_u64);
+        self.duration = info.get("time_total").and_then(Value::as_f64);
+        self.content_type = string("content_type");
+        self.exit_code = info
+            .get("exitcode")
+            .and_then(Value::as_u64)
+            .and_then(|code| u32::try_from(code).ok());
+
+        self.error = match self.exit_code {
+            Some(0) | None => None,
+            Some(code) => {
+                Some(string("errormsg").unwrap_or(format!("curl failed with exit code {}", code)))
+            }
+        };
+    }
+
+    /// `downloaded`, `failed`, or for URLs that weren't downloaded,
+    /// `skipped` or `duplicate`.
+    pub fn status(&self) -> &'static str {
+        match self.skipped {
+            Some(Skip::Existing) => "skipped",
+            Some(Skip::Duplicate) => "duplicate",
+            None if self.succeeded() => "downloaded",
+            None => "failed",
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        Value::Object(vec![
+            ("url".to_string(), self.url.as_str().into()),
+            ("status".to_string(), self.status().into()),
+            ("output".to_string(), self.output.clone().into()),
+            ("http_status".to_string(), self.http_status.into()),
+            (
+                "effective_url".to_string(),
+                self.effective_url.clone().into(),
+            ),
+            ("bytes".to_string(), self.bytes.into()),
+            ("duration".to_string(), self.duration.into()),
+            ("content_type".to_string(), self.content_type.clone().into()),
+            ("exit_code".to_string(), self.exit_code.into()),
+            ("error".to_string(), self.error.clone().into()),
+        ])
+    }
+}
+
+/// Writes `transfers` to `path`, or to standard output if it is `-`.
+pub fn write_report(
+    path: &str,
+    transfers: &[Transfer],
+    format: ReportFormat,
+) -> Result<(), String> {
+    let mut text = match format {
+        ReportFormat::Json => {
+            json::Value::Array(transfers.iter().map(Transfer::to_json).collect()).to_string()
+        }
+        ReportFormat::Ndjson => transfers
+            .iter()
+            .map(|transfer| transfer.to_json().to_string())
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+    if !text.is_empty() {
+        text.push('\n');
+    }
+
+    let result = if path == "-" {
+        io::stdout().write_all(text.as_bytes())
+    } else {
+        fs::write(path, text)
+    };
+    result.map_err(|e| format!("Failed to write report to {}: {}", path, e))
+}
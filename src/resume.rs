@@ -0,0 +1,128 @@
+//! Support for `--continue`: downloads go to `<output>.part`, the response
+//! headers are kept in `<output>.part.headers`, and a rerun asks only for
+//! the missing bytes with an `If-Range` validator so that a file that
+//! changed on the server is fetched again from the start.
+
+use std::fs;
+use std::io;
+
+pub fn part_path(output: &str) -> String {
+    format!("{}.part", output)
+}
+
+pub fn headers_path(output: &str) -> String {
+    format!("{}.part.headers", output)
+}
+
+/// Response header names and values, in the order they were received.
+pub type Headers = Vec<(String, String)>;
+
+/// A `.part` file left behind by an earlier run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialDownload {
+    pub size: u64,
+    /// Strong ETag or Last-Modified date to send as `If-Range`.
+    pub validator: Option<String>,
+    /// Full size of the remote file, if the server said.
+    pub total: Option<u64>,
+    /// Status of the last response, if its headers were saved.
+    pub status: Option<u16>,
+}
+
+impl PartialDownload {
+    /// Looks for a partial download of `output`.
+    pub fn find(output: &str) -> Option<PartialDownload> {
+        let size = fs::metadata(part_path(output)).ok()?.len();
+        let (status, headers) = read_headers(&headers_path(output)).unwrap_or_default();
+
+        Some(PartialDownload {
+            size,
+            validator: validator(&headers),
+            total: total_size(&headers),
+            status,
+        })
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.total == Some(self.size)
+    }
+}
+
+/// Removes the `.part` file and its headers, so the download starts over.
+pub fn discard(output: &str) -> Result<(), String> {
+    for path in [part_path(output), headers_path(output)] {
+        match fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Failed to remove {}: {}", path, e)),
+        }
+    }
+    Ok(())
+}
+
+/// Reads the status and headers of the last response from a
+/// `--dump-header` file.
+pub fn read_headers(path: &str) -> Option<(Option<u16>, Headers)> {
+    fs::read_to_string(path)
+        .ok()
+        .map(|text| last_response_headers(&text))
+}
+
+/// Returns the status and headers of the last response in a
+/// `--dump-header` file, which holds one block per redirect.
+fn last_response_headers(text: &str) -> (Option<u16>, Headers) {
+    let mut status = None;
+    let mut headers = Vec::new();
+
+    for line in text.lines() {
+        let line = line.trim_end();
+        if line.starts_with("HTTP/") {
+            status = line.split_whitespace().nth(1).and_then(|s| s.parse().ok());
+            headers.clear();
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    (status, headers)
+}
+
+pub fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Picks the value for `If-Range`. Weak ETags can't be used there.
+pub fn validator(headers: &[(String, String)]) -> Option<String> {
+    header(headers, "ETag")
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| header(headers, "Last-Modified"))
+        .map(str::to_string)
+}
+
+/// Size of the whole remote file, from `Content-Range` or `Content-Length`.
+pub fn total_size(headers: &[(String, String)]) -> Option<u64> {
+    match header(headers, "Content-Range") {
+        Some(range) => range.rsplit_once('/')?.1.trim().parse().ok(),
+        None => header(headers, "Content-Length")?.parse().ok(),
+    }
+}
+
+/// Parses the first byte position of a `Content-Range: bytes N-M/T` header.
+pub fn range_start(headers: &[(String, String)]) -> Option<u64> {
+    let range = header(headers, "Content-Range")?;
+    let range = range.trim().strip_prefix("bytes")?.trim_start();
+    range.split('-').next()?.parse().ok()
+}
+
+/// Writes `headers` to `path` in the same layout curl's `--dump-header` uses.
+pub fn save_headers(path: &str, status: u16, headers: &[(String, String)]) -> io::Result<()> {
+    let mut text = format!("HTTP/1.1 {}\r\n", status);
+    for (name, value) in headers {
+        text.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    text.push_str("\r\n");
+    fs::write(path, text)
+}
@@ -0,0 +1,106 @@
+//! wcurl's own retry policy, for failures curl's `--retry` leaves alone and
+//! for the native backend: exponential backoff with jitter, bounded by
+//! `--retries` and `--retry-max-time`.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::curl::{Capability, CurlInfo};
+use crate::plan::DownloadPlan;
+
+/// Longest wait between retries, matching curl's ten minute cap.
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(600);
+
+/// curl exit codes for failures that are usually temporary but which
+/// curl's `--retry` only retries with `--retry-all-errors`: HTTP/2 errors,
+/// a transfer cut short, an empty reply and send or receive errors.
+const TRANSIENT_EXIT_CODES: [u32; 6] = [16, 18, 52, 55, 56, 92];
+
+/// curl's exit code for a connection that couldn't be made.
+const COULDNT_CONNECT: u32 = 7;
+
+/// Hands out the waits between tries: `--retry-delay` if given, otherwise
+/// one second doubling each time, each wait picked at random from its upper
+/// half so that clients failing together don't retry together.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    retries_left: u32,
+    next: Duration,
+    fixed: Option<Duration>,
+    deadline: Option<Instant>,
+}
+
+impl Backoff {
+    /// `max_time` is measured from now; 0 means no limit, as with curl.
+    pub fn new(retries: u32, delay: Option<u32>, max_time: Option<u32>) -> Self {
+        Backoff {
+            retries_left: retries,
+            next: Duration::from_secs(1),
+            fixed: delay.map(|secs| Duration::from_secs(secs.into())),
+            deadline: max_time
+                .filter(|&secs| secs > 0)
+                .map(|secs| Instant::now() + Duration::from_secs(secs.into())),
+        }
+    }
+
+    pub fn for_plan(plan: &DownloadPlan) -> Self {
+        Backoff::new(plan.retries, plan.retry_delay, plan.retry_max_time)
+    }
+
+    pub fn retries_left(&self) -> u32 {
+        self.retries_left
+    }
+
+    /// How long to wait before the next try, or `None` when there are no
+    /// retries left or the wait would run past `--retry-max-time`. A
+    /// server's `Retry-After` is used instead of the computed wait when
+    /// given as `hint`.
+    pub fn next_delay(&mut self, hint: Option<Duration>) -> Option<Duration> {
+        if self.retries_left == 0 {
+            return None;
+        }
+
+        let wait = match (hint, self.fixed) {
+            (Some(hint), _) => hint,
+            (None, Some(fixed)) => fixed,
+            (None, None) => jitter(self.next),
+        }
+        .min(MAX_RETRY_DELAY);
+
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() + wait > deadline)
+        {
+            return None;
+        }
+
+        self.retries_left -= 1;
+        self.next = (self.next * 2).min(MAX_RETRY_DELAY);
+        Some(wait)
+    }
+}
+
+/// A random duration between half of `delay` and `delay`.
+fn jitter(delay: Duration) -> Duration {
+    let mut hasher = RandomState::new().build_hasher();
+    if let Ok(now) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        hasher.write_u128(now.as_nanos());
+    }
+    let half = delay / 2;
+    let millis = u64::try_from(half.as_millis()).unwrap_or(u64::MAX);
+    half + Duration::from_millis(hasher.finish() % (millis + 1))
+}
+
+/// Whether wcurl should retry a transfer that failed with curl exit code
+/// `code`, because curl didn't. Errors curl retries itself, such as
+/// timeouts and HTTP 5xx, have already had their retries.
+pub fn wcurl_retries(plan: &DownloadPlan, curl: &CurlInfo, code: u32) -> bool {
+    if plan.retry_all_errors {
+        return !curl.supports(Capability::RetryAllErrors);
+    }
+    if code == COULDNT_CONNECT {
+        return plan.retry_connrefused && !curl.supports(Capability::RetryConnrefused);
+    }
+    TRANSIENT_EXIT_CODES.contains(&code)
+}
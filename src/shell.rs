@@ -0,0 +1,135 @@
+/// Splits `s` into words the way a POSIX shell would, honouring single
+/// quotes, double quotes and backslash escapes. No expansion is performed.
+pub fn split(s: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' | '\n' => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') => {}
+                Some(escaped) => {
+                    word.push(escaped);
+                    in_word = true;
+                }
+                None => return Err(format!("Trailing backslash in {:?}", s)),
+            },
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(quoted) => word.push(quoted),
+                        None => return Err(format!("Unbalanced single quote in {:?}", s)),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('\n') => {}
+                            Some(escaped @ ('$' | '`' | '"' | '\\')) => word.push(escaped),
+                            Some(other) => {
+                                word.push('\\');
+                                word.push(other);
+                            }
+                            None => return Err(format!("Unbalanced double quote in {:?}", s)),
+                        },
+                        Some(quoted) => word.push(quoted),
+                        None => return Err(format!("Unbalanced double quote in {:?}", s)),
+                    }
+                }
+            }
+            _ => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    if in_word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+/// Quotes `s` so that [`split`] (or a POSIX shell) reads it back as one word.
+pub fn quote(s: &str) -> String {
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c);
+
+    if !s.is_empty() && s.chars().all(is_plain) {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(s: &str) -> Vec<String> {
+        split(s).unwrap()
+    }
+
+    #[test]
+    fn splits_on_whitespace() {
+        assert_eq!(words("  -H  a\tb\nc "), ["-H", "a", "b", "c"]);
+        assert!(words("").is_empty());
+        assert!(words(" \t\n").is_empty());
+    }
+
+    #[test]
+    fn single_quotes_are_literal() {
+        assert_eq!(words(r"'a b' 'c\d' '$x'"), ["a b", r"c\d", "$x"]);
+        assert_eq!(words("''"), [""]);
+        assert_eq!(words("a'b c'd"), ["ab cd"]);
+    }
+
+    #[test]
+    fn double_quotes_keep_most_backslashes() {
+        assert_eq!(
+            words(r#""a b" "c\"d" "e\\f" "g\h""#),
+            ["a b", "c\"d", r"e\f", r"g\h"]
+        );
+        assert_eq!(words(r#""\$HOME" "\`""#), ["$HOME", "`"]);
+        assert_eq!(words("\"a\\\nb\""), ["ab"]);
+    }
+
+    #[test]
+    fn backslash_escapes_outside_quotes() {
+        assert_eq!(words(r"a\ b c\'d \\"), ["a b", "c'd", r"\"]);
+        assert_eq!(words("a\\\nb"), ["ab"]);
+    }
+
+    #[test]
+    fn rejects_unbalanced_quotes() {
+        assert!(split("'abc").unwrap_err().contains("single quote"));
+        assert!(split("\"abc").unwrap_err().contains("double quote"));
+        assert!(split("\"abc\\").unwrap_err().contains("double quote"));
+        assert!(split("abc\\").unwrap_err().contains("Trailing backslash"));
+    }
+
+    #[test]
+    fn quote_round_trips() {
+        for s in ["plain", "", "a b", "it's", "$HOME", "a\"b\\c", "x\ny"] {
+            assert_eq!(words(&quote(s)), [s]);
+        }
+        assert_eq!(
+            quote("https://example.com/a.zip"),
+            "https://example.com/a.zip"
+        );
+    }
+}
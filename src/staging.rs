@@ -0,0 +1,148 @@
+//! Downloads are written to a temporary file in the same directory as their
+//! output and only renamed into place once the transfer succeeded and any
+//! checksum matched, so an output path never holds a partial file.
+
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::Path;
+use std::process;
+
+use crate::config::OnConflict;
+use crate::filename::numbered_filename;
+
+/// How many numbered names `--on-conflict rename` tries.
+const RENAME_ATTEMPTS: u32 = 1000;
+
+/// Temporary file for the `idx`th download of this run, hidden next to
+/// `output` so the final rename stays on one filesystem.
+pub fn temp_path(output: &str, idx: usize) -> String {
+    let path = Path::new(output);
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let temp = format!(".{}.{}-{}.wcurl-tmp", name, process::id(), idx);
+
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.join(temp).to_string_lossy().into_owned(),
+        _ => temp,
+    }
+}
+
+/// Moves a finished download to `output`, resolving an existing file there
+/// according to `on_conflict`. Returns the path the download ended up at,
+/// or `None` if it was skipped and its temporary file removed.
+pub fn commit(
+    staging: &str,
+    output: &str,
+    on_conflict: OnConflict,
+) -> Result<Option<String>, String> {
+    let from = Path::new(staging);
+    let error = |to: &Path, e: io::Error| {
+        format!(
+            "Failed to rename {} to {}: {}",
+            from.display(),
+            to.display(),
+            e
+        )
+    };
+    let exists = |e: &io::Error| e.kind() == io::ErrorKind::AlreadyExists;
+
+    match on_conflict {
+        OnConflict::Overwrite => fs::rename(from, output)
+            .map(|()| Some(output.to_string()))
+            .map_err(|e| error(Path::new(output), e)),
+        OnConflict::Fail => match rename_no_clobber(from, Path::new(output)) {
+            Ok(()) => Ok(Some(output.to_string())),
+            Err(e) if exists(&e) => {
+                remove(staging)?;
+                Err(format!("{} already exists", output))
+            }
+            Err(e) => Err(error(Path::new(output), e)),
+        },
+        OnConflict::Skip => match rename_no_clobber(from, Path::new(output)) {
+            Ok(()) => Ok(Some(output.to_string())),
+            Err(e) if exists(&e) => {
+                eprintln!("Note: {} already exists, skipping", output);
+                remove(staging)?;
+                Ok(None)
+            }
+            Err(e) => Err(error(Path::new(output), e)),
+        },
+        OnConflict::Rename => {
+            let mut candidate = output.to_string();
+            for n in 1..=RENAME_ATTEMPTS {
+                match rename_no_clobber(from, Path::new(&candidate)) {
+                    Ok(()) => return Ok(Some(candidate)),
+                    Err(e) if exists(&e) => candidate = numbered_name(output, n),
+                    Err(e) => return Err(error(Path::new(&candidate), e)),
+                }
+            }
+            Err(format!("Failed to find a free name for {}", output))
+        }
+    }
+}
+
+/// `dir/name (n).ext` for `dir/name.ext`, see [`numbered_filename`].
+pub fn numbered_name(output: &str, n: u32) -> String {
+    let path = Path::new(output);
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(numbered_filename(&name, n))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Moves a download whose checksum didn't match to `<output>.corrupt`, so
+/// it can be inspected without being mistaken for a good file.
+pub fn quarantine(staging: &str, output: &str) -> Result<String, String> {
+    let corrupt = format!("{}.corrupt", output);
+    fs::rename(staging, &corrupt)
+        .map_err(|e| format!("Failed to rename {} to {}: {}", staging, corrupt, e))?;
+    Ok(corrupt)
+}
+
+/// Removes a temporary file, ignoring one that was never created.
+pub fn remove(path: &str) -> Result<(), String> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(format!("Failed to remove {}: {}", path, e)),
+    }
+}
+
+/// Creates an empty temporary file at `path`, failing if anything is
+/// already there. wcurl reserves each temporary file this way before curl
+/// writes to it, so no two runs ever share one, whatever the curl version.
+pub fn reserve(path: &str) -> Result<(), String> {
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .map(drop)
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::AlreadyExists => format!("Temporary file {} already exists", path),
+            _ => format!("Failed to create {}: {}", path, e),
+        })
+}
+
+/// Renames `from` to `to`, failing instead of replacing an existing `to`.
+fn rename_no_clobber(from: &Path, to: &Path) -> io::Result<()> {
+    // A hard link is only created if `to` doesn't exist, which a rename
+    // can't promise. Where links don't work, claim `to` with an exclusive
+    // create and then rename over the empty file we own.
+    match fs::hard_link(from, to) {
+        Ok(()) => fs::remove_file(from),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Err(e),
+        Err(_) => {
+            OpenOptions::new().write(true).create_new(true).open(to)?;
+            let renamed = fs::rename(from, to);
+            if renamed.is_err() {
+                let _ = fs::remove_file(to);
+            }
+            renamed
+        }
+    }
+}
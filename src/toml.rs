@@ -0,0 +1,374 @@
+//! A parser for the small subset of TOML used by wcurl's configuration file:
+//! top-level `key = value` pairs whose values are strings, integers, booleans
+//! or arrays of those. Tables are not supported.
+
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Integer(_) => "integer",
+            Value::Boolean(_) => "boolean",
+            Value::Array(_) => "array",
+        }
+    }
+}
+
+/// A `key = value` pair along with the line it started on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub key: String,
+    pub value: Value,
+    pub line: usize,
+}
+
+/// Parses `text`, returning errors as `(line, message)`.
+pub fn parse(text: &str) -> Result<Vec<Entry>, (usize, String)> {
+    let mut parser = Parser {
+        chars: text.char_indices().peekable(),
+        line: 1,
+    };
+    let mut entries: Vec<Entry> = Vec::new();
+
+    loop {
+        parser.skip_blank_lines();
+        let line = parser.line;
+
+        match parser.peek() {
+            None => break,
+            Some('[') => return Err((line, "tables are not supported".to_string())),
+            Some(_) => {}
+        }
+
+        let key = parser.key()?;
+        parser.skip_spaces();
+        if parser.next() != Some('=') {
+            return Err((line, format!("expected '=' after key '{}'", key)));
+        }
+        parser.skip_spaces();
+        let value = parser.value()?;
+        parser.end_of_line()?;
+
+        if entries.iter().any(|entry| entry.key == key) {
+            return Err((line, format!("duplicate key '{}'", key)));
+        }
+        entries.push(Entry { key, value, line });
+    }
+
+    Ok(entries)
+}
+
+struct Parser<'a> {
+    chars: Peekable<CharIndices<'a>>,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, c)| c)
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next().map(|(_, c)| c);
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        c
+    }
+
+    /// Like [`Parser::next`], but stops at the end of the line without
+    /// consuming it, so errors are reported on the line they occur on.
+    fn next_in_line(&mut self) -> Option<char> {
+        match self.peek() {
+            None | Some('\n') => None,
+            Some(_) => self.next(),
+        }
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, (usize, String)> {
+        Err((self.line, message.to_string()))
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.next();
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.next();
+            }
+        }
+    }
+
+    fn skip_blank_lines(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            match self.peek() {
+                Some('\n' | '\r') => {
+                    self.next();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), (usize, String)> {
+        self.skip_spaces();
+        self.skip_comment();
+        if self.peek() == Some('\r') {
+            self.next();
+        }
+        match self.next() {
+            None | Some('\n') => Ok(()),
+            Some(c) => self.error(&format!("unexpected '{}' after value", c)),
+        }
+    }
+
+    fn key(&mut self) -> Result<String, (usize, String)> {
+        match self.peek() {
+            Some('"') => {
+                self.next();
+                self.basic_string()
+            }
+            Some('\'') => {
+                self.next();
+                self.literal_string()
+            }
+            _ => {
+                let mut key = String::new();
+                while let Some(c) = self.peek() {
+                    if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                        key.push(c);
+                        self.next();
+                    } else {
+                        break;
+                    }
+                }
+                if key.is_empty() {
+                    return self.error("expected a key");
+                }
+                Ok(key)
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, (usize, String)> {
+        match self.peek() {
+            Some('"') => {
+                self.next();
+                self.basic_string().map(Value::String)
+            }
+            Some('\'') => {
+                self.next();
+                self.literal_string().map(Value::String)
+            }
+            Some('[') => {
+                self.next();
+                self.array()
+            }
+            Some('t' | 'f') => {
+                let word = self.bare_word();
+                match word.as_str() {
+                    "true" => Ok(Value::Boolean(true)),
+                    "false" => Ok(Value::Boolean(false)),
+                    _ => self.error(&format!("invalid value '{}'", word)),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '-' || c == '+' => {
+                let word = self.bare_word();
+                word.replace('_', "")
+                    .parse()
+                    .map(Value::Integer)
+                    .or_else(|_| self.error(&format!("invalid integer '{}'", word)))
+            }
+            _ => self.error("expected a value"),
+        }
+    }
+
+    fn bare_word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '+' {
+                word.push(c);
+                self.next();
+            } else {
+                break;
+            }
+        }
+        word
+    }
+
+    fn array(&mut self) -> Result<Value, (usize, String)> {
+        let mut items = Vec::new();
+
+        loop {
+            self.skip_blank_lines();
+            if self.peek() == Some(']') {
+                self.next();
+                return Ok(Value::Array(items));
+            }
+
+            items.push(self.value()?);
+
+            self.skip_blank_lines();
+            match self.next() {
+                Some(',') => {}
+                Some(']') => return Ok(Value::Array(items)),
+                _ => return self.error("expected ',' or ']' in array"),
+            }
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, (usize, String)> {
+        let mut s = String::new();
+
+        loop {
+            match self.next_in_line() {
+                None => return self.error("unterminated string"),
+                Some('"') => return Ok(s),
+                Some('\\') => match self.next_in_line() {
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('n') => s.push('\n'),
+                    Some('t') => s.push('\t'),
+                    Some('r') => s.push('\r'),
+                    Some('u') => s.push(self.unicode_escape(4)?),
+                    Some('U') => s.push(self.unicode_escape(8)?),
+                    _ => return self.error("invalid escape sequence in string"),
+                },
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, (usize, String)> {
+        let mut s = String::new();
+
+        loop {
+            match self.next_in_line() {
+                None => return self.error("unterminated string"),
+                Some('\'') => return Ok(s),
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn unicode_escape(&mut self, digits: usize) -> Result<char, (usize, String)> {
+        let hex: String = (0..digits).filter_map(|_| self.next_in_line()).collect();
+        u32::from_str_radix(&hex, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .map_or_else(|| self.error("invalid unicode escape in string"), Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(text: &str) -> Vec<(String, Value)> {
+        parse(text)
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.key, entry.value))
+            .collect()
+    }
+
+    fn error(text: &str) -> (usize, String) {
+        parse(text).unwrap_err()
+    }
+
+    #[test]
+    fn parses_scalars() {
+        let text = "# comment\n\nretries = 3\nparallel = false # trailing\ncurl = \"/usr/bin/curl\"\nbig = 1_000\n";
+        assert_eq!(
+            values(text),
+            [
+                ("retries".to_string(), Value::Integer(3)),
+                ("parallel".to_string(), Value::Boolean(false)),
+                (
+                    "curl".to_string(),
+                    Value::String("/usr/bin/curl".to_string())
+                ),
+                ("big".to_string(), Value::Integer(1000)),
+            ]
+        );
+    }
+
+    #[test]
+    fn records_entry_lines() {
+        let entries = parse("a = 1\n\n# c\nb = [\n  1,\n]\nc = 2\r\n").unwrap();
+        let lines: Vec<usize> = entries.iter().map(|entry| entry.line).collect();
+        assert_eq!(lines, [1, 4, 7]);
+    }
+
+    #[test]
+    fn parses_strings() {
+        assert_eq!(
+            values(r#"a = "x\"y\\z\n\u00e9""#)[0].1,
+            Value::String("x\"y\\z\n\u{e9}".to_string())
+        );
+        assert_eq!(
+            values(r"a = 'C:\dir\file'")[0].1,
+            Value::String(r"C:\dir\file".to_string())
+        );
+        assert_eq!(values("\"quoted key\" = 1")[0].0, "quoted key");
+    }
+
+    #[test]
+    fn parses_arrays() {
+        let text = "opts = [\n  \"-H\", # header\n  'X: y',\n  [1, true],\n]\nempty = []\n";
+        assert_eq!(
+            values(text),
+            [
+                (
+                    "opts".to_string(),
+                    Value::Array(vec![
+                        Value::String("-H".to_string()),
+                        Value::String("X: y".to_string()),
+                        Value::Array(vec![Value::Integer(1), Value::Boolean(true)]),
+                    ])
+                ),
+                ("empty".to_string(), Value::Array(Vec::new())),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_duplicate_keys() {
+        assert_eq!(
+            error("a = 1\nb = 2\na = 3\n"),
+            (3, "duplicate key 'a'".to_string())
+        );
+    }
+
+    #[test]
+    fn reports_error_lines() {
+        assert_eq!(error("a = 1\nb = \"open\nc = 2\n").0, 2);
+        assert_eq!(error("a = 1\nb = 'open\n").0, 2);
+        assert_eq!(error("a = 1\nb = \"bad \\\n").0, 2);
+        assert_eq!(error("a = 1\nb = \"open").0, 2);
+        assert_eq!(error("a = 1\n\nb =\n").0, 3);
+        assert_eq!(error("a = 1\nb = 2 3\n").0, 2);
+        assert_eq!(error("a = [\n1\n2]\n").0, 3);
+        assert_eq!(error("a = tru\n").1, "invalid value 'tru'");
+        assert_eq!(
+            error("a = 1\n[table]\n"),
+            (2, "tables are not supported".to_string())
+        );
+    }
+}